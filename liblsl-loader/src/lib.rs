@@ -0,0 +1,313 @@
+//! Runtime loading of the liblsl C API, shared by lsl-recorder and webcam-py.
+//!
+//! liblsl is loaded instead of being linked, so recordings that do not publish
+//! streams of their own work without it. Each caller names the environment
+//! variable that overrides the library location, `LSL_RECORDER_LIBLSL` for
+//! lsl-recorder and `WEBCAM_PY_LIBLSL` for webcam-py, and may add directories to
+//! search; the first copy that loads is kept for the rest of the process.
+
+use std::{
+    ffi::{c_char, c_double, c_int, c_void, CString},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use libloading::Library;
+
+/// `cft_double64` in `lsl_channel_format_t`.
+pub const CHANNEL_FORMAT_DOUBLE: c_int = 2;
+
+/// `cft_string` in `lsl_channel_format_t`.
+pub const CHANNEL_FORMAT_STRING: c_int = 3;
+
+/// `LSL_IRREGULAR_RATE`.
+pub const IRREGULAR_RATE: c_double = 0.0;
+
+/// File name of liblsl on this platform, found through the system library path.
+#[cfg(target_os = "windows")]
+pub const LIBRARY_NAME: &str = "lsl.dll";
+#[cfg(target_os = "macos")]
+pub const LIBRARY_NAME: &str = "liblsl.dylib";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub const LIBRARY_NAME: &str = "liblsl.so";
+
+pub type StreamInfoHandle = *mut c_void;
+pub type OutletHandle = *mut c_void;
+pub type XmlHandle = *mut c_void;
+
+/// The parts of the liblsl C API the recorders use.
+pub struct Api {
+    pub create_streaminfo: unsafe extern "C" fn(
+        *const c_char,
+        *const c_char,
+        c_int,
+        c_double,
+        c_int,
+        *const c_char,
+    ) -> StreamInfoHandle,
+    pub destroy_streaminfo: unsafe extern "C" fn(StreamInfoHandle),
+    pub get_desc: unsafe extern "C" fn(StreamInfoHandle) -> XmlHandle,
+    pub append_child: unsafe extern "C" fn(XmlHandle, *const c_char) -> XmlHandle,
+    pub append_child_value:
+        unsafe extern "C" fn(XmlHandle, *const c_char, *const c_char) -> XmlHandle,
+    pub create_outlet: unsafe extern "C" fn(StreamInfoHandle, c_int, c_int) -> OutletHandle,
+    pub destroy_outlet: unsafe extern "C" fn(OutletHandle),
+    pub push_sample_strt:
+        unsafe extern "C" fn(OutletHandle, *const *const c_char, c_double) -> c_int,
+    pub push_sample_dtp:
+        unsafe extern "C" fn(OutletHandle, *const c_double, c_double, c_int) -> c_int,
+    pub wait_for_consumers: unsafe extern "C" fn(OutletHandle, c_double) -> c_int,
+    pub local_clock: unsafe extern "C" fn() -> c_double,
+}
+
+impl Api {
+    /// Current time on the LSL clock, in seconds.
+    pub fn local_clock(&self) -> f64 {
+        // SAFETY: no arguments, no preconditions
+        unsafe { (self.local_clock)() }
+    }
+}
+
+/// liblsl, once it has been loaded successfully. Never unloaded.
+static API: Mutex<Option<&'static Api>> = Mutex::new(None);
+
+/// Load liblsl, or return the copy loaded earlier.
+///
+/// The library is looked up in this order:
+/// 1. the path in the environment variable `env_var`,
+/// 2. each of `dirs`,
+/// 3. the system library path.
+pub fn load(env_var: &str, dirs: &[&Path]) -> Result<&'static Api, std::io::Error> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if let Some(path) = std::env::var_os(env_var) {
+        candidates.push(PathBuf::from(path));
+    }
+    candidates.extend(dirs.iter().map(|dir| dir.join(LIBRARY_NAME)));
+    candidates.push(PathBuf::from(LIBRARY_NAME));
+
+    let mut api = API
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Some(api) = *api {
+        return Ok(api);
+    }
+
+    let mut errors = Vec::new();
+    for candidate in &candidates {
+        // SAFETY: liblsl has no initialization routines with preconditions
+        match unsafe { Library::new(candidate) } {
+            Ok(library) => {
+                let loaded: &'static Api = Box::leak(Box::new(load_api(library)?));
+                *api = Some(loaded);
+                return Ok(loaded);
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!(
+            "liblsl not found (set {}; tried {})",
+            env_var,
+            errors.join(", ")
+        ),
+    ))
+}
+
+fn load_api(library: Library) -> Result<Api, std::io::Error> {
+    // the library is leaked, so the function pointers stay valid
+    let library: &'static Library = Box::leak(Box::new(library));
+    macro_rules! symbol {
+        ($name:literal) => {
+            // SAFETY: the signatures match the liblsl C API
+            *unsafe { library.get(concat!($name, "\0").as_bytes()) }
+                .map_err(|e| std::io::Error::other(format!("liblsl is missing {}: {}", $name, e)))?
+        };
+    }
+    Ok(Api {
+        create_streaminfo: symbol!("lsl_create_streaminfo"),
+        destroy_streaminfo: symbol!("lsl_destroy_streaminfo"),
+        get_desc: symbol!("lsl_get_desc"),
+        append_child: symbol!("lsl_append_child"),
+        append_child_value: symbol!("lsl_append_child_value"),
+        create_outlet: symbol!("lsl_create_outlet"),
+        destroy_outlet: symbol!("lsl_destroy_outlet"),
+        push_sample_strt: symbol!("lsl_push_sample_strt"),
+        push_sample_dtp: symbol!("lsl_push_sample_dtp"),
+        wait_for_consumers: symbol!("lsl_wait_for_consumers"),
+        local_clock: symbol!("lsl_local_clock"),
+    })
+}
+
+/// `value` as a C string for liblsl, rejecting interior NUL characters.
+pub fn c_string(value: &str) -> Result<CString, std::io::Error> {
+    CString::new(value).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("'{}' contains a NUL character", value.escape_debug()),
+        )
+    })
+}
+
+/// Header of a stream published through [`Outlet::new`].
+pub struct StreamHeader<'a> {
+    pub name: &'a str,
+    pub kind: &'a str,
+    pub channel_count: usize,
+    /// Samples per second, or [`IRREGULAR_RATE`].
+    pub nominal_rate: f64,
+    /// One of the `CHANNEL_FORMAT_*` constants.
+    pub channel_format: c_int,
+    pub source_id: &'a str,
+    /// Written into `<desc>` as `<channels><channel><label>`, unless empty.
+    pub channel_labels: &'a [&'a str],
+    /// Written into `<desc>`, one child element per entry.
+    pub desc: &'a [(&'a str, &'a str)],
+}
+
+/// A liblsl stream outlet, destroyed when dropped.
+pub struct Outlet {
+    api: &'static Api,
+    handle: OutletHandle,
+    channel_count: usize,
+}
+
+// SAFETY: liblsl outlets may be used from any thread
+unsafe impl Send for Outlet {}
+unsafe impl Sync for Outlet {}
+
+impl Outlet {
+    /// Create an outlet for the stream described by `header`.
+    pub fn new(api: &'static Api, header: &StreamHeader) -> Result<Self, std::io::Error> {
+        let name = c_string(header.name)?;
+        let kind = c_string(header.kind)?;
+        let source_id = c_string(header.source_id)?;
+        let channel_count = c_int::try_from(header.channel_count).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Too many channels")
+        })?;
+        let labels = header
+            .channel_labels
+            .iter()
+            .map(|label| c_string(label))
+            .collect::<Result<Vec<_>, _>>()?;
+        let desc = header
+            .desc
+            .iter()
+            .map(|(key, value)| Ok((c_string(key)?, c_string(value)?)))
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+        let [channels_tag, channel_tag, label_tag] =
+            ["channels", "channel", "label"].map(|tag| CString::new(tag).unwrap());
+
+        // SAFETY: all strings are valid and NUL-terminated; liblsl copies the
+        // stream info into the outlet, so it can be destroyed right away
+        let handle = unsafe {
+            let info = (api.create_streaminfo)(
+                name.as_ptr(),
+                kind.as_ptr(),
+                channel_count,
+                header.nominal_rate,
+                header.channel_format,
+                source_id.as_ptr(),
+            );
+            if info.is_null() {
+                return Err(std::io::Error::other("Failed to create stream info"));
+            }
+            let xml = (api.get_desc)(info);
+            if !labels.is_empty() {
+                let channels = (api.append_child)(xml, channels_tag.as_ptr());
+                for label in &labels {
+                    let channel = (api.append_child)(channels, channel_tag.as_ptr());
+                    (api.append_child_value)(channel, label_tag.as_ptr(), label.as_ptr());
+                }
+            }
+            for (key, value) in &desc {
+                (api.append_child_value)(xml, key.as_ptr(), value.as_ptr());
+            }
+            let handle = (api.create_outlet)(info, 0, 360);
+            (api.destroy_streaminfo)(info);
+            handle
+        };
+        if handle.is_null() {
+            return Err(std::io::Error::other("Failed to create outlet"));
+        }
+
+        Ok(Outlet {
+            api,
+            handle,
+            channel_count: header.channel_count,
+        })
+    }
+
+    /// Current time on the LSL clock, in seconds.
+    pub fn local_clock(&self) -> f64 {
+        self.api.local_clock()
+    }
+
+    /// Wait up to `timeout` until an inlet has subscribed to the stream.
+    pub fn wait_for_consumers(&self, timeout: Duration) -> bool {
+        // SAFETY: the outlet is alive until drop
+        unsafe { (self.api.wait_for_consumers)(self.handle, timeout.as_secs_f64()) != 0 }
+    }
+
+    /// Push a sample of a `CHANNEL_FORMAT_STRING` stream, stamped with
+    /// `timestamp` on the LSL clock.
+    pub fn push_strings(&self, sample: &[&str], timestamp: f64) -> Result<(), std::io::Error> {
+        self.check_channels(sample.len())?;
+        let values = sample
+            .iter()
+            .map(|value| c_string(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pointers = values
+            .iter()
+            .map(|value| value.as_ptr())
+            .collect::<Vec<_>>();
+        // SAFETY: the sample holds one valid string per channel and the outlet
+        // is alive until drop
+        check_push(unsafe {
+            (self.api.push_sample_strt)(self.handle, pointers.as_ptr(), timestamp)
+        })
+    }
+
+    /// Push a sample of a `CHANNEL_FORMAT_DOUBLE` stream, stamped with
+    /// `timestamp` on the LSL clock and sent right away.
+    pub fn push_doubles(&self, sample: &[f64], timestamp: f64) -> Result<(), std::io::Error> {
+        self.check_channels(sample.len())?;
+        // SAFETY: the sample holds one value per channel and the outlet is alive
+        // until drop
+        check_push(unsafe {
+            (self.api.push_sample_dtp)(self.handle, sample.as_ptr(), timestamp, 1)
+        })
+    }
+
+    // liblsl reads one value per channel, whatever the length of the sample
+    fn check_channels(&self, len: usize) -> Result<(), std::io::Error> {
+        if len != self.channel_count {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Sample has {} values for {} channels",
+                    len, self.channel_count
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Outlet {
+    fn drop(&mut self) {
+        // SAFETY: the outlet is not used after this
+        unsafe { (self.api.destroy_outlet)(self.handle) };
+    }
+}
+
+fn check_push(result: c_int) -> Result<(), std::io::Error> {
+    if result < 0 {
+        return Err(std::io::Error::other(format!(
+            "liblsl error {} while pushing a sample",
+            result
+        )));
+    }
+    Ok(())
+}
@@ -1,9 +1,21 @@
-# import the contents of the Rust library into the Python extension
-from .lsl_recorder import *
-from .lsl_recorder import __all__
-
-# optional: include the documentation from the Rust module
-from .lsl_recorder import __doc__  # noqa: F401
-
-# set gstreamer plugin environment variable to site-packages/psydk/.dylibs/
-import platform
+# import the contents of the Rust library into the Python extension
+from .lsl_recorder import *
+from .lsl_recorder import __all__
+
+# optional: include the documentation from the Rust module
+from .lsl_recorder import __doc__  # noqa: F401
+
+from ._async import AsyncLSLStreamRecorder
+
+__all__ = [*__all__, "AsyncLSLStreamRecorder"]
+
+# stop recorders that are still running when the interpreter exits,
+# so LabRecorderCLI does not keep the file open after a crash
+import atexit
+
+from .lsl_recorder import stop_all as _stop_all
+
+atexit.register(_stop_all)
+
+# set gstreamer plugin environment variable to site-packages/psydk/.dylibs/
+import platform
@@ -0,0 +1,67 @@
+import asyncio
+import functools
+
+from .lsl_recorder import LSLStreamRecorder
+
+
+class AsyncLSLStreamRecorder:
+    """asyncio interface to LSLStreamRecorder.
+
+    Takes the same arguments as LSLStreamRecorder, but the recording only starts
+    when `start()` is awaited (or the `async with` block is entered). Starting,
+    stopping and splitting run in the default executor, so the event loop keeps
+    running while LabRecorderCLI waits for streams or finalizes the file.
+    """
+
+    def __init__(self, *args, **kwargs):
+        self._args = args
+        self._kwargs = kwargs
+        self._recorder = None
+
+    @property
+    def recorder(self):
+        """The underlying LSLStreamRecorder, or None before `start()`."""
+        return self._recorder
+
+    async def start(self):
+        if self._recorder is not None:
+            raise RuntimeError("Recorder has already been started")
+        loop = asyncio.get_running_loop()
+        self._recorder = await loop.run_in_executor(
+            None, functools.partial(LSLStreamRecorder, *self._args, **self._kwargs)
+        )
+        return self
+
+    async def stop(self, timeout=10.0):
+        if self._recorder is None:
+            raise RuntimeError("Recorder has not been started")
+        loop = asyncio.get_running_loop()
+        return await loop.run_in_executor(None, self._recorder.stop, timeout)
+
+    async def split(self):
+        if self._recorder is None:
+            raise RuntimeError("Recorder has not been started")
+        loop = asyncio.get_running_loop()
+        return await loop.run_in_executor(None, self._recorder.split)
+
+    def status(self):
+        if self._recorder is None:
+            raise RuntimeError("Recorder has not been started")
+        return self._recorder.status()
+
+    def annotate(self, marker, timestamp=None):
+        if self._recorder is None:
+            raise RuntimeError("Recorder has not been started")
+        return self._recorder.annotate(marker, timestamp)
+
+    async def __aenter__(self):
+        return await self.start()
+
+    async def __aexit__(self, exc_type, exc_value, traceback):
+        try:
+            await self.stop()
+        except Exception:
+            # don't replace an exception that is already propagating
+            if exc_type is None:
+                raise
+        return False
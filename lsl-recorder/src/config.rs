@@ -0,0 +1,374 @@
+//! Reading the LabRecorder GUI configuration (`LabRecorder.cfg`).
+//!
+//! Only the settings that matter for a scripted recording are interpreted: the
+//! storage location (legacy `StorageLocation`, or BIDS `StudyRoot` and
+//! `PathTemplate`), `SessionBlocks`, `RequiredStreams`, `BidsModalities` and the
+//! remote control socket. Everything else is ignored.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use pyo3::{PyResult, exceptions::PyRuntimeError, pyclass, pymethods};
+
+use crate::{RecorderOptions, rcs::RcsFilename};
+
+/// Template LabRecorder uses when neither `StorageLocation` nor `PathTemplate` is set.
+pub const DEFAULT_BIDS_TEMPLATE: &str =
+    "sub-%p/ses-%s/%m/sub-%p_ses-%s_task-%b[_acq-%a]_run-%r_%m.xdf";
+
+/// How long `from_config` waits for the streams when no timeout is given.
+pub const DEFAULT_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Settings of a recording started from a `LabRecorder.cfg` that are not in the file.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    /// Placeholder values, and a root or template replacing the configured ones.
+    pub filename: RcsFilename,
+    /// Streams to record in addition to the `RequiredStreams`.
+    pub predicates: Vec<String>,
+    pub timeout: Option<Duration>,
+    pub cli_path: Option<PathBuf>,
+    pub options: RecorderOptions,
+}
+
+#[pyclass(get_all)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabRecorderConfig {
+    /// Full legacy path with placeholders; takes precedence over the BIDS settings.
+    pub storage_location: Option<String>,
+    pub study_root: Option<String>,
+    pub path_template: Option<String>,
+    pub session_blocks: Vec<String>,
+    /// Streams in the `"Name (Hostname)"` format of the LabRecorder stream list.
+    pub required_streams: Vec<String>,
+    pub bids_modalities: Vec<String>,
+    pub rcs_enabled: Option<bool>,
+    pub rcs_port: Option<u16>,
+}
+
+impl LabRecorderConfig {
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut config = LabRecorderConfig::default();
+        for line in text.lines() {
+            let line = line.trim_start_matches('\u{feff}').trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('[') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "StorageLocation" => config.storage_location = Some(unquote(value)),
+                "StudyRoot" => config.study_root = Some(unquote(value)),
+                "PathTemplate" => config.path_template = Some(unquote(value)),
+                "SessionBlocks" => config.session_blocks = parse_list(value),
+                "RequiredStreams" => config.required_streams = parse_list(value),
+                "BidsModalities" => config.bids_modalities = parse_list(value),
+                "RCSEnabled" => config.rcs_enabled = Some(value == "1"),
+                "RCSPort" => config.rcs_port = value.parse().ok(),
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// Search predicates matching the required streams.
+    pub fn required_predicates(&self) -> Vec<String> {
+        self.required_streams
+            .iter()
+            .map(|stream| stream_predicate(stream))
+            .collect()
+    }
+
+    /// File name of a recording, with the placeholders filled in from `fields`.
+    ///
+    /// `fields.root` and `fields.template` replace `StudyRoot` and `PathTemplate`.
+    /// A block (`fields.task`) must be one of the configured `SessionBlocks`; without
+    /// one, the first block is used.
+    pub fn filename(&self, fields: &RcsFilename) -> Result<PathBuf, std::io::Error> {
+        let block = match &fields.task {
+            Some(block) => {
+                if !self.session_blocks.is_empty() && !self.session_blocks.contains(block) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "Block '{}' is not one of the SessionBlocks ({})",
+                            block,
+                            self.session_blocks.join(", ")
+                        ),
+                    ));
+                }
+                block.clone()
+            }
+            None => self
+                .session_blocks
+                .first()
+                .cloned()
+                .unwrap_or_else(|| "Default".to_string()),
+        };
+
+        // only the template has placeholders, the study root is used as is
+        let (root, template) = match (&self.storage_location, &fields.root, &fields.template) {
+            (Some(location), None, None) => (None, location.as_str()),
+            (_, root, template) => {
+                let root = root
+                    .clone()
+                    .or_else(|| self.study_root.clone())
+                    .map(PathBuf::from)
+                    .unwrap_or_else(default_study_root);
+                let template = template
+                    .as_deref()
+                    .or(self.path_template.as_deref())
+                    .unwrap_or(DEFAULT_BIDS_TEMPLATE);
+                (Some(root), template)
+            }
+        };
+
+        let run = fields.run.unwrap_or(1);
+        let acquisition = fields.acquisition.clone().unwrap_or_default();
+        let template = remove_empty_optional(template, &acquisition);
+        let path = expand(&template, |placeholder| match placeholder {
+            'p' => Some(
+                fields
+                    .participant
+                    .clone()
+                    .unwrap_or_else(|| "P001".to_string()),
+            ),
+            's' => Some(fields.session.clone().unwrap_or_else(|| "S001".to_string())),
+            'b' => Some(block.clone()),
+            'a' => Some(acquisition.clone()),
+            'm' => Some(fields.modality.clone().unwrap_or_else(|| "eeg".to_string())),
+            'r' => Some(format!("{:03}", run)),
+            'n' => Some(run.to_string()),
+            _ => None,
+        });
+        Ok(match root {
+            Some(root) => root.join(path),
+            None => PathBuf::from(path),
+        })
+    }
+}
+
+#[pymethods]
+impl LabRecorderConfig {
+    /// Read a `LabRecorder.cfg` file.
+    #[staticmethod]
+    #[pyo3(name = "load")]
+    fn py_load(path: String) -> PyResult<Self> {
+        Self::load(Path::new(&path))
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to read config {}: {}", path, e)))
+    }
+}
+
+/// XPath predicate for a stream listed as `"Name (Hostname)"`.
+fn stream_predicate(stream: &str) -> String {
+    match stream.trim().rsplit_once(" (") {
+        Some((name, hostname)) => format!(
+            "name=\"{}\" and hostname=\"{}\"",
+            name.trim(),
+            hostname.trim_end_matches(')').trim()
+        ),
+        None => format!("name=\"{}\"", stream.trim()),
+    }
+}
+
+fn default_study_root() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    home.join("Documents").join("CurrentStudy")
+}
+
+/// Resolve `[...]` sections, which are dropped when their placeholder is empty.
+fn remove_empty_optional(path: &str, acquisition: &str) -> String {
+    let mut result = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('[') {
+        let Some(end) = rest[start..].find(']') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let optional = &rest[start + 1..start + end];
+        if !(optional.contains("%a") && acquisition.is_empty()) {
+            result.push_str(optional);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Replace the `%x` placeholders of `template` in a single pass, so values that
+/// contain `%` are not expanded again. Unknown placeholders are kept.
+fn expand(template: &str, value: impl Fn(char) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek().copied().filter(|_| c == '%').and_then(&value) {
+            Some(value) => {
+                chars.next();
+                result.push_str(&value);
+            }
+            None => result.push(c),
+        }
+    }
+    result
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').to_string()
+}
+
+/// Parse `"A", "B", "C"` (or an unquoted `A, B, C`) into its items.
+fn parse_list(value: &str) -> Vec<String> {
+    if !value.contains('"') {
+        return value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+    value
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\u{feff}; LabRecorder.cfg
+[General]
+StudyRoot=/data/study
+PathTemplate=\"sub-%p/sub-%p_task-%b[_acq-%a]_run-%r_%m.xdf\"
+SessionBlocks=\"rest\", \"task\"
+RequiredStreams=\"EEG (amp-pc)\", \"Markers\"
+BidsModalities=eeg, ieeg
+
+[RemoteControl]
+RCSEnabled=1
+RCSPort=22345
+";
+
+    fn fields() -> RcsFilename {
+        RcsFilename {
+            participant: Some("P007".to_string()),
+            run: Some(2),
+            ..RcsFilename::default()
+        }
+    }
+
+    #[test]
+    fn parses_keys_in_all_sections() {
+        let config = LabRecorderConfig::parse(CONFIG);
+        assert_eq!(
+            config,
+            LabRecorderConfig {
+                storage_location: None,
+                study_root: Some("/data/study".to_string()),
+                path_template: Some("sub-%p/sub-%p_task-%b[_acq-%a]_run-%r_%m.xdf".to_string()),
+                session_blocks: vec!["rest".to_string(), "task".to_string()],
+                required_streams: vec!["EEG (amp-pc)".to_string(), "Markers".to_string()],
+                bids_modalities: vec!["eeg".to_string(), "ieeg".to_string()],
+                rcs_enabled: Some(true),
+                rcs_port: Some(22345),
+            }
+        );
+    }
+
+    #[test]
+    fn converts_required_streams_to_predicates() {
+        let config = LabRecorderConfig::parse(CONFIG);
+        assert_eq!(
+            config.required_predicates(),
+            ["name=\"EEG\" and hostname=\"amp-pc\"", "name=\"Markers\""]
+        );
+        assert_eq!(
+            stream_predicate(" Eye Tracker (lab-pc) "),
+            "name=\"Eye Tracker\" and hostname=\"lab-pc\""
+        );
+    }
+
+    #[test]
+    fn expands_the_legacy_storage_location() {
+        let config = LabRecorderConfig::parse(
+            "StorageLocation=/data/%b/recording_%n.xdf\nSessionBlocks=\"rest\"",
+        );
+        assert_eq!(
+            config.filename(&fields()).unwrap(),
+            PathBuf::from("/data/rest/recording_2.xdf")
+        );
+    }
+
+    #[test]
+    fn drops_the_acquisition_group_when_empty() {
+        let config = LabRecorderConfig::parse(CONFIG);
+        assert_eq!(
+            config.filename(&fields()).unwrap(),
+            PathBuf::from("/data/study/sub-P007/sub-P007_task-rest_run-002_eeg.xdf")
+        );
+
+        let mut fields = fields();
+        fields.acquisition = Some("highres".to_string());
+        fields.task = Some("task".to_string());
+        assert_eq!(
+            config.filename(&fields).unwrap(),
+            PathBuf::from("/data/study/sub-P007/sub-P007_task-task_acq-highres_run-002_eeg.xdf")
+        );
+    }
+
+    #[test]
+    fn uses_the_default_bids_template() {
+        let fields = RcsFilename {
+            root: Some("/data".to_string()),
+            ..fields()
+        };
+        assert_eq!(
+            LabRecorderConfig::default().filename(&fields).unwrap(),
+            PathBuf::from(
+                "/data/sub-P007/ses-S001/eeg/sub-P007_ses-S001_task-Default_run-002_eeg.xdf"
+            )
+        );
+    }
+
+    #[test]
+    fn does_not_expand_placeholders_in_values() {
+        let config = LabRecorderConfig::parse(CONFIG);
+        let mut fields = fields();
+        fields.participant = Some("100%run%r".to_string());
+        assert_eq!(
+            config.filename(&fields).unwrap(),
+            PathBuf::from("/data/study/sub-100%run%r/sub-100%run%r_task-rest_run-002_eeg.xdf")
+        );
+        assert_eq!(expand("50%x", |_| None), "50%x");
+        assert_eq!(expand("end%", |_| Some("v".to_string())), "end%");
+    }
+
+    #[test]
+    fn rejects_unknown_blocks() {
+        let config = LabRecorderConfig::parse(CONFIG);
+        let mut fields = fields();
+        fields.task = Some("sleep".to_string());
+        let error = config.filename(&fields).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn parses_quoted_and_unquoted_lists() {
+        assert_eq!(parse_list("\"a, b\", \"c\""), ["a, b", "c"]);
+        assert_eq!(parse_list("a, b,,c "), ["a", "b", "c"]);
+        assert!(parse_list("").is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+//! Locating the LabRecorderCLI executable.
+//!
+//! The executable is looked up in this order:
+//! 1. an explicit path passed by the caller,
+//! 2. the `LSL_RECORDER_CLI` environment variable,
+//! 3. the directories on `PATH`,
+//! 4. the binary bundled in an `app` directory, preferring a per-platform
+//!    subdirectory such as `app/linux-x86_64/`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use pyo3::{pyclass, pymethods};
+
+/// Environment variable that overrides the LabRecorderCLI location.
+pub const CLI_ENV_VAR: &str = "LSL_RECORDER_CLI";
+
+/// How long the preflight check waits for LabRecorderCLI to print its usage.
+const USAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn cli_file_name() -> String {
+    format!("LabRecorderCLI{}", std::env::consts::EXE_SUFFIX)
+}
+
+/// Result of a successful preflight check.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliInfo {
+    pub path: PathBuf,
+    /// Version LabRecorderCLI printed when asked for it, if it printed one.
+    ///
+    /// Released builds only print their usage, so this is usually `None`.
+    #[pyo3(get)]
+    pub version: Option<String>,
+}
+
+#[pymethods]
+impl CliInfo {
+    #[getter(path)]
+    fn py_path(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.version {
+            Some(version) => format!(
+                "CliInfo(path='{}', version='{}')",
+                self.path.display(),
+                version
+            ),
+            None => format!("CliInfo(path='{}', version=None)", self.path.display()),
+        }
+    }
+}
+
+/// Find LabRecorderCLI, see the module documentation for the search order.
+///
+/// `bundled_dir` is the `app` directory shipped with the package. Without it, the
+/// `app` directories next to the current executable and in the working directory
+/// are tried.
+pub fn find_cli(
+    explicit: Option<&Path>,
+    bundled_dir: Option<&Path>,
+) -> Result<PathBuf, std::io::Error> {
+    if let Some(path) = explicit {
+        return existing(path.to_path_buf(), "cli_path");
+    }
+
+    if let Some(path) = std::env::var_os(CLI_ENV_VAR) {
+        return existing(PathBuf::from(path), CLI_ENV_VAR);
+    }
+
+    let mut tried = Vec::new();
+    if let Some(path) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&path) {
+            let candidate = dir.join(cli_file_name());
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+        }
+        tried.push("PATH".to_string());
+    }
+
+    let bundled_dirs = match bundled_dir {
+        Some(dir) => vec![dir.to_path_buf()],
+        None => std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("app")))
+            .into_iter()
+            .chain(Some(PathBuf::from("app")))
+            .collect(),
+    };
+    let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+    for dir in bundled_dirs {
+        for candidate in [
+            dir.join(&platform).join(cli_file_name()),
+            dir.join(cli_file_name()),
+        ] {
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+            tried.push(candidate.display().to_string());
+        }
+    }
+
+    Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!(
+            "LabRecorderCLI not found (set {} or pass cli_path; tried {})",
+            CLI_ENV_VAR,
+            tried.join(", ")
+        ),
+    ))
+}
+
+/// Check that `cli` exists, is executable and is LabRecorderCLI.
+///
+/// LabRecorderCLI has no version flag; given too few arguments it prints its usage
+/// (`Usage: LabRecorderCLI outputfile.xdf 'searchstr' ...`) and exits with 1. The
+/// check requires that usage text, and reports a version only if one was printed.
+pub fn check_cli(cli: &Path) -> Result<CliInfo, std::io::Error> {
+    let path = existing(cli.to_path_buf(), "cli_path")?;
+    if !is_executable(&path) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is not executable", path.display()),
+        ));
+    }
+
+    let mut child = Command::new(&path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let start_time = Instant::now();
+    while child.try_wait()?.is_none() {
+        if start_time.elapsed() > USAGE_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("{} did not exit after printing its usage", path.display()),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let output = child.wait_with_output()?;
+    if output.status.code().is_none() {
+        return Err(std::io::Error::other(format!(
+            "{} crashed ({})",
+            path.display(),
+            output.status
+        )));
+    }
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if !is_cli_usage(&text) {
+        return Err(std::io::Error::other(format!(
+            "{} does not look like LabRecorderCLI (it did not print its usage)",
+            path.display()
+        )));
+    }
+
+    Ok(CliInfo {
+        path,
+        version: parse_version(&text),
+    })
+}
+
+/// Whether `text` contains the usage LabRecorderCLI prints without arguments.
+fn is_cli_usage(text: &str) -> bool {
+    text.lines().map(str::trim).any(|line| {
+        line.starts_with("Usage:") && line.contains(".xdf") && line.contains("searchstr")
+    })
+}
+
+/// The first dotted version number on a line that mentions a version.
+fn parse_version(text: &str) -> Option<String> {
+    text.lines()
+        .filter(|line| line.to_lowercase().contains("version"))
+        .flat_map(str::split_whitespace)
+        .map(|word| {
+            word.trim_start_matches(['v', 'V'])
+                .trim_end_matches([',', ';', ')'])
+        })
+        .find(|word| {
+            word.contains('.')
+                && word
+                    .split('.')
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(str::to_string)
+}
+
+fn existing(path: PathBuf, source: &str) -> Result<PathBuf, std::io::Error> {
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "LabRecorderCLI from {} not found at {}",
+                source,
+                path.display()
+            ),
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USAGE: &str = "Usage: ./LabRecorderCLI outputfile.xdf 'searchstr' ['searchstr2' ...]\n\n\
+        searchstr can be anything accepted by lsl_resolve_bypred\n";
+
+    #[test]
+    fn recognizes_the_usage_text() {
+        assert!(is_cli_usage(USAGE));
+        assert!(!is_cli_usage("Python 3.12.1\n"));
+        assert!(!is_cli_usage("usage: ls [OPTION]... [FILE]...\n"));
+    }
+
+    #[test]
+    fn reports_no_version_when_none_is_printed() {
+        assert_eq!(parse_version(USAGE), None);
+        assert_eq!(parse_version("Usage: LabRecorderCLI 2.xdf\n"), None);
+    }
+
+    #[test]
+    fn parses_a_printed_version() {
+        assert_eq!(
+            parse_version("LabRecorder version v1.16.4\n"),
+            Some("1.16.4".to_string())
+        );
+        assert_eq!(
+            parse_version("liblsl version: 1.16, protocol 110\n"),
+            Some("1.16".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_executables_that_are_not_labrecorder() {
+        let error = check_cli(Path::new("/bin/echo")).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("does not look like LabRecorderCLI")
+        );
+    }
+}
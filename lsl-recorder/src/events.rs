@@ -0,0 +1,91 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Why a recording ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// `stop()` was called.
+    Requested,
+    /// The recording reached `max_duration`.
+    MaxDuration,
+    /// The output file grew beyond `max_file_size`.
+    MaxFileSize,
+    /// Free space on the output disk dropped below `min_free_disk`.
+    LowDiskSpace,
+    /// A scheduled recording reached its `stop_after` duration.
+    Scheduled,
+    /// `split()` closed the file and the recording continued in the next run.
+    Split,
+    /// The recorder was dropped while recording, without a `stop()`.
+    Dropped,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StopReason::Requested => "requested",
+            StopReason::MaxDuration => "max_duration",
+            StopReason::MaxFileSize => "max_file_size",
+            StopReason::LowDiskSpace => "low_disk_space",
+            StopReason::Scheduled => "scheduled",
+            StopReason::Split => "split",
+            StopReason::Dropped => "dropped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Something that happened during a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecorderEvent {
+    /// LabRecorderCLI started collecting data.
+    Started,
+    /// A recording guard fired and is stopping the recording.
+    GuardTriggered(StopReason),
+    /// The recording continued into a new file after a gap without data.
+    Split { filename: PathBuf, gap: Duration },
+    /// The recording was stopped.
+    Stopped(StopReason),
+}
+
+impl fmt::Display for RecorderEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecorderEvent::Started => write!(f, "started"),
+            RecorderEvent::GuardTriggered(reason) => write!(f, "guard_triggered:{}", reason),
+            RecorderEvent::Split { filename, gap } => write!(
+                f,
+                "split:{} (gap {:.3} s)",
+                filename.display(),
+                gap.as_secs_f64()
+            ),
+            RecorderEvent::Stopped(reason) => write!(f, "stopped:{}", reason),
+        }
+    }
+}
+
+/// Queue of timestamped events shared between the recorder and its threads.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventLog {
+    events: Arc<Mutex<Vec<(SystemTime, RecorderEvent)>>>,
+}
+
+impl EventLog {
+    pub(crate) fn push(&self, event: RecorderEvent) {
+        if let Ok(mut events) = self.events.lock() {
+            events.push((SystemTime::now(), event));
+        }
+    }
+
+    /// Remove and return all events recorded so far.
+    pub(crate) fn take(&self) -> Vec<(SystemTime, RecorderEvent)> {
+        self.events
+            .lock()
+            .map(|mut events| std::mem::take(&mut *events))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,232 @@
+//! Watchdog that stops a recording when it runs too long, grows too large or
+//! the output disk fills up.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::events::StopReason;
+
+/// How often the watchdog checks the output file.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Limits that stop a recording automatically.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordingGuards {
+    /// Stop after the recording has been running this long, across all of its
+    /// split files.
+    pub max_duration: Option<Duration>,
+    /// Stop when the file currently being written exceeds this many bytes. When
+    /// the recording is split, each file is checked on its own.
+    pub max_file_size: Option<u64>,
+    /// Stop when free space on the output disk drops below this many bytes.
+    pub min_free_disk: Option<u64>,
+}
+
+impl RecordingGuards {
+    pub fn is_empty(&self) -> bool {
+        self.max_duration.is_none() && self.max_file_size.is_none() && self.min_free_disk.is_none()
+    }
+
+    /// Check the limits once, returning the reason to stop if one was exceeded.
+    ///
+    /// `filename` is the file currently being written and `elapsed` the time
+    /// since the recording started.
+    pub fn check(&self, filename: &Path, elapsed: Duration) -> Option<StopReason> {
+        if let Some(max_duration) = self.max_duration
+            && elapsed >= max_duration
+        {
+            return Some(StopReason::MaxDuration);
+        }
+        if let Some(max_file_size) = self.max_file_size
+            && let Ok(metadata) = std::fs::metadata(filename)
+            && metadata.len() > max_file_size
+        {
+            return Some(StopReason::MaxFileSize);
+        }
+        if let Some(min_free_disk) = self.min_free_disk
+            && let Ok(free) = free_disk_space(&output_dir(filename))
+            && free < min_free_disk
+        {
+            return Some(StopReason::LowDiskSpace);
+        }
+        None
+    }
+}
+
+/// Run the guards on a background thread until `done` is set.
+///
+/// `filename` returns the file currently being written, which changes when the
+/// recording is split into runs. `on_trigger` is called at most once, with the
+/// reason the recording has to stop.
+pub(crate) fn spawn_watchdog(
+    guards: RecordingGuards,
+    filename: impl Fn() -> PathBuf + Send + 'static,
+    done: Arc<AtomicBool>,
+    on_trigger: impl FnOnce(StopReason) + Send + 'static,
+) {
+    std::thread::spawn(move || {
+        let start_time = Instant::now();
+        while !done.load(Ordering::Relaxed) {
+            if let Some(reason) = guards.check(&filename(), start_time.elapsed()) {
+                on_trigger(reason);
+                return;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+fn output_dir(filename: &Path) -> PathBuf {
+    match filename.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Free space in bytes available to the current user on the disk holding `path`.
+#[cfg(unix)]
+pub fn free_disk_space(path: &Path) -> Result<u64, std::io::Error> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Free space in bytes available to the current user on the disk holding `path`.
+#[cfg(windows)]
+pub fn free_disk_space(path: &Path) -> Result<u64, std::io::Error> {
+    use std::os::windows::ffi::OsStrExt;
+
+    unsafe extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            free_bytes_available: *mut u64,
+            total_bytes: *mut u64,
+            total_free_bytes: *mut u64,
+        ) -> i32;
+    }
+
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_of_size(name: &str, size: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "lsl-recorder-guard-{}-{}.xdf",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        path
+    }
+
+    #[test]
+    fn stops_at_the_max_duration() {
+        let guards = RecordingGuards {
+            max_duration: Some(Duration::from_secs(60)),
+            ..RecordingGuards::default()
+        };
+        let path = Path::new("missing.xdf");
+        assert_eq!(guards.check(path, Duration::from_secs(59)), None);
+        assert_eq!(
+            guards.check(path, Duration::from_secs(60)),
+            Some(StopReason::MaxDuration)
+        );
+    }
+
+    #[test]
+    fn stops_when_the_current_file_exceeds_the_max_size() {
+        let guards = RecordingGuards {
+            max_file_size: Some(1000),
+            ..RecordingGuards::default()
+        };
+        let full = file_of_size("full", 1000);
+        let over = file_of_size("over", 1001);
+
+        assert_eq!(guards.check(&full, Duration::ZERO), None);
+        assert_eq!(
+            guards.check(&over, Duration::ZERO),
+            Some(StopReason::MaxFileSize)
+        );
+        // a file that doesn't exist yet has nothing to check
+        assert_eq!(guards.check(Path::new("missing.xdf"), Duration::ZERO), None);
+
+        std::fs::remove_file(full).unwrap();
+        std::fs::remove_file(over).unwrap();
+    }
+
+    #[test]
+    fn stops_when_the_disk_runs_low() {
+        let path = std::env::temp_dir().join("recording.xdf");
+        let free = free_disk_space(&std::env::temp_dir()).unwrap();
+        assert!(free > 0);
+
+        let enough = RecordingGuards {
+            min_free_disk: Some(1),
+            ..RecordingGuards::default()
+        };
+        assert_eq!(enough.check(&path, Duration::ZERO), None);
+        let too_little = RecordingGuards {
+            min_free_disk: Some(u64::MAX),
+            ..RecordingGuards::default()
+        };
+        assert_eq!(
+            too_little.check(&path, Duration::ZERO),
+            Some(StopReason::LowDiskSpace)
+        );
+        // a bare file name is on the disk of the working directory
+        assert_eq!(
+            too_little.check(Path::new("recording.xdf"), Duration::ZERO),
+            Some(StopReason::LowDiskSpace)
+        );
+    }
+
+    #[test]
+    fn checks_the_duration_first() {
+        let guards = RecordingGuards {
+            max_duration: Some(Duration::from_secs(1)),
+            max_file_size: Some(0),
+            min_free_disk: Some(u64::MAX),
+        };
+        let over = file_of_size("first", 10);
+        assert_eq!(
+            guards.check(&over, Duration::from_secs(1)),
+            Some(StopReason::MaxDuration)
+        );
+        assert_eq!(
+            guards.check(&over, Duration::ZERO),
+            Some(StopReason::MaxFileSize)
+        );
+        std::fs::remove_file(over).unwrap();
+        assert!(!guards.is_empty());
+        assert!(RecordingGuards::default().is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+//! Checksums of finished recordings.
+//!
+//! A cleanly finished file gets two sidecars: `<file>.sha256` in the format of
+//! `sha256sum`, and `<file>.json` with the size, the checksum and a summary of
+//! the recorded streams. `verify_integrity` re-checks a file against them later,
+//! for example on the archive server. The format is defined in the
+//! `recording-sidecars` crate, which webcam-py uses for its video files too, so
+//! both can be verified here.
+
+use std::path::Path;
+
+use pyo3::{pyclass, pymethods};
+use recording_sidecars::Expected;
+pub use recording_sidecars::{checksum_path, sha256_file, summary_path};
+use serde_json::{Value, json};
+
+use crate::StreamStatus;
+
+/// Result of checking a file against its `.sha256` sidecar.
+#[pyclass(get_all)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub path: String,
+    pub expected_sha256: String,
+    /// `None` if the file is missing.
+    pub actual_sha256: Option<String>,
+    /// Size recorded in the `.json` sidecar, if there is one.
+    pub expected_size: Option<u64>,
+    pub actual_size: Option<u64>,
+    /// Whether the checksum (and size, if known) match.
+    pub valid: bool,
+}
+
+#[pymethods]
+impl IntegrityReport {
+    fn __bool__(&self) -> bool {
+        self.valid
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "IntegrityReport(path='{}', valid={})",
+            self.path,
+            if self.valid { "True" } else { "False" }
+        )
+    }
+}
+
+/// Write the `.sha256` and `.json` sidecars of a finished recording.
+pub(crate) fn write_sidecars(
+    path: &Path,
+    sha256: &str,
+    streams: &[StreamStatus],
+) -> Result<(), std::io::Error> {
+    recording_sidecars::write(path, sha256, streams_json(streams))
+}
+
+/// Check `path` against its sidecars.
+///
+/// Fails if the `.sha256` sidecar is missing or unreadable; a missing or modified
+/// recording is reported as invalid.
+pub fn verify_integrity(path: &Path) -> Result<IntegrityReport, std::io::Error> {
+    let Expected {
+        sha256: expected_sha256,
+        size: expected_size,
+    } = recording_sidecars::read(path)?;
+
+    let actual_size = std::fs::metadata(path).ok().map(|metadata| metadata.len());
+    let actual_sha256 = match actual_size {
+        Some(_) => Some(sha256_file(path)?),
+        None => None,
+    };
+    let valid = actual_sha256.as_deref() == Some(expected_sha256.as_str())
+        && expected_size.is_none_or(|size| Some(size) == actual_size);
+
+    Ok(IntegrityReport {
+        path: path.to_string_lossy().into_owned(),
+        expected_sha256,
+        actual_sha256,
+        expected_size,
+        actual_size,
+        valid,
+    })
+}
+
+/// Summary of the recorded streams, as written to the sidecar and the journal.
+pub(crate) fn streams_json(streams: &[StreamStatus]) -> Vec<Value> {
+    streams
+        .iter()
+        .map(|stream| {
+            json!({
+                "name": stream.name,
+                "hostname": stream.hostname,
+                "state": stream.state.to_string(),
+            })
+        })
+        .collect()
+}
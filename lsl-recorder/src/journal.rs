@@ -0,0 +1,67 @@
+//! Append-only journal of finished recordings.
+//!
+//! Every file a recorder closes adds one JSON object on its own line, so data
+//! managers can reconcile which recordings exist and spot files that went
+//! missing or were modified afterwards.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::json;
+
+use crate::{StopReason, StreamStatus, integrity::streams_json};
+
+/// A file that is being recorded and has no journal entry yet.
+#[derive(Debug, Clone)]
+pub(crate) struct OpenFile {
+    pub(crate) filename: PathBuf,
+    pub(crate) started_at: SystemTime,
+}
+
+/// How a file ended, as written to the journal.
+pub(crate) struct ClosedFile<'a> {
+    pub(crate) file: OpenFile,
+    pub(crate) stopped_at: SystemTime,
+    pub(crate) predicates: &'a [String],
+    pub(crate) streams: &'a [StreamStatus],
+    pub(crate) stop_reason: Option<StopReason>,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) sha256: Option<String>,
+}
+
+/// Append the entry for `closed` to the journal at `journal`.
+pub(crate) fn append(journal: &Path, closed: &ClosedFile) -> Result<(), std::io::Error> {
+    let size = std::fs::metadata(&closed.file.filename)
+        .ok()
+        .map(|metadata| metadata.len());
+    let entry = json!({
+        "filename": closed.file.filename.to_string_lossy(),
+        "predicates": closed.predicates,
+        "streams": streams_json(closed.streams),
+        "started_at": unix_time(closed.file.started_at),
+        "stopped_at": unix_time(closed.stopped_at),
+        "stop_reason": closed.stop_reason.map(|reason| reason.to_string()),
+        "exit_code": closed.exit_code,
+        "size": size,
+        "sha256": closed.sha256,
+    });
+
+    if let Some(parent) = journal.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    // a single write per line, so concurrent recorders don't interleave entries
+    let mut file = OpenOptions::new().create(true).append(true).open(journal)?;
+    file.write_all(format!("{}\n", entry).as_bytes())
+}
+
+fn unix_time(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
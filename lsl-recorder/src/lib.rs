@@ -1,21 +1,281 @@
 use std::{
-    io::{BufRead, Write},
+    collections::BTreeMap,
+    path::{Path, PathBuf},
     process::Child,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError, TryLockError, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use pyo3::{
-    Bound, Py, PyAny, PyRef, PyRefMut, PyResult, Python,
-    exceptions::PyRuntimeError,
-    ffi::c_str,
-    pyclass, pymethods, pymodule,
-    types::{PyAnyMethods, PyModule, PyModuleMethods, PyType},
+    Bound, Py, PyAny, PyRef, PyResult, Python,
+    exceptions::{PyRuntimeError, PyValueError},
+    pyclass, pyfunction, pymethods, pymodule,
+    types::{PyAnyMethods, PyModule, PyModuleMethods},
+    wrap_pyfunction,
 };
 
+mod config;
+mod discovery;
+mod events;
+mod guard;
+mod integrity;
+mod journal;
+mod lifetime;
+mod monitor;
+mod network;
+mod outlet;
+mod rcs;
+mod rotation;
+mod schedule;
+mod status;
+
+use events::EventLog;
+use journal::{ClosedFile, OpenFile};
+use monitor::Monitor;
+use network::TempConfig;
+use outlet::StringOutlet;
+
+pub use config::{
+    ConfigOverrides, DEFAULT_BIDS_TEMPLATE, DEFAULT_START_TIMEOUT, LabRecorderConfig,
+};
+pub use discovery::{CLI_ENV_VAR, CliInfo, check_cli, find_cli};
+pub use events::{RecorderEvent, StopReason};
+pub use guard::{RecordingGuards, free_disk_space};
+pub use integrity::{IntegrityReport, checksum_path, sha256_file, summary_path, verify_integrity};
+pub use lifetime::{LeftoverProcess, StopOutcome, leftover_processes};
+pub use network::{IpVersion, LSL_CONFIG_ENV_VAR, NetworkConfig};
+pub use outlet::LIBLSL_ENV_VAR;
+pub use rcs::{DEFAULT_RCS_PORT, RcsClient, RcsCommand, RcsFilename};
+pub use rotation::next_run_filename;
+pub use schedule::{Schedule, ScheduledRecorder, StartAt};
+pub use status::{RecorderState, RecorderStatus, StreamState, StreamStatus};
+
+/// Optional behaviour of an `LSLStreamRecorder`.
+#[derive(Debug, Clone, Default)]
+pub struct RecorderOptions {
+    /// Limits that stop the recording automatically.
+    pub guards: RecordingGuards,
+    /// Split the recording into a new numbered run at this interval.
+    pub split_every: Option<Duration>,
+    /// liblsl network settings passed to LabRecorderCLI as a generated `lsl_api.cfg`.
+    ///
+    /// Cannot be combined with `marker_stream` or `metadata`: their outlets live in
+    /// this process, whose liblsl does not see the generated file.
+    pub network: Option<NetworkConfig>,
+    /// Name of a marker stream owned by the recorder and recorded with the other
+    /// streams, for `annotate()` and the start and stop markers.
+    pub marker_stream: Option<String>,
+    /// Session metadata (participant, protocol version, notes, ...) recorded as the
+    /// header of an extra "SessionInfo" stream.
+    pub metadata: BTreeMap<String, String>,
+    /// JSON-lines file that gets an entry for every finished file.
+    pub journal: Option<PathBuf>,
+    /// Write `.sha256` and `.json` sidecars for every cleanly finished file.
+    pub checksums: bool,
+}
+
+/// How long `stop()` waits for LabRecorderCLI to finish the file by default.
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long `Drop` waits for LabRecorderCLI to finish the file before killing it.
+const DROP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Time for the stop marker to reach LabRecorderCLI before the file is closed.
+const MARKER_FLUSH_DELAY: Duration = Duration::from_millis(200);
+
+/// Marker pushed when LabRecorderCLI has started writing a file.
+pub const START_MARKER: &str = "recording_started";
+
+/// Marker pushed right before a file is closed.
+pub const STOP_MARKER: &str = "recording_stopped";
+
+/// Name and type of the stream that carries the session metadata.
+pub const SESSION_INFO_STREAM: &str = "SessionInfo";
+
+/// Recorders that are still alive, so they can be stopped at interpreter exit.
+static RECORDERS: Mutex<Vec<Weak<Shared>>> = Mutex::new(Vec::new());
+
+/// Everything needed to (re)start LabRecorderCLI.
+struct Launch {
+    cli_path: PathBuf,
+    predicates: Vec<String>,
+    timeout: Duration,
+    lsl_config: Option<TempConfig>,
+    markers: Option<StringOutlet>,
+    /// The session info stream and the single sample written to every file.
+    session_info: Option<(StringOutlet, String)>,
+    journal: Option<PathBuf>,
+    checksums: bool,
+}
+
+/// State shared between a recorder, its clones and its background threads.
+///
+/// Background threads only hold weak references, so the child process is shut
+/// down as soon as the last `LSLStreamRecorder` handle is dropped.
+struct Shared {
+    process: Mutex<Child>,
+    monitor: Mutex<Arc<Monitor>>,
+    launch: Launch,
+    files: Mutex<Vec<PathBuf>>,
+    /// The file being recorded, until it has been closed.
+    open_file: Mutex<Option<OpenFile>>,
+    /// Threads still hashing and journaling files closed by `split()`.
+    closing: Mutex<Vec<JoinHandle<()>>>,
+    state: Mutex<RecorderState>,
+    started_at: Instant,
+    stopped_at: Mutex<Option<Instant>>,
+    stop_reason: Mutex<Option<StopReason>>,
+    events: EventLog,
+    done: Arc<AtomicBool>,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        let process = self
+            .process
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let result = lifetime::shutdown(process, DROP_GRACE_PERIOD);
+        if let Err(e) = &result {
+            eprintln!("Failed to shut down LabRecorderCLI: {}", e);
+        }
+        let exit_code = exit_code(process);
+        // a LabRecorderCLI that exited on its own was not stopped by the drop
+        let stop_reason = lock(&self.stop_reason).or(match result {
+            Ok(StopOutcome::AlreadyExited(_)) => None,
+            _ => Some(StopReason::Dropped),
+        });
+        let closing = self.close_file(exit_code, stop_reason, is_clean(&result));
+        self.finish_closing(closing);
+    }
+}
+
+impl Shared {
+    /// Check without waiting whether LabRecorderCLI has exited and return its exit
+    /// code if so. Returns `None` while it runs or while a stop or split holds it.
+    fn poll_exit(&self) -> Option<Option<i32>> {
+        let mut process = match self.process.try_lock() {
+            Ok(process) => process,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        let status = process.try_wait().ok()??;
+        self.mark_exited();
+        Some(status.code())
+    }
+
+    /// Record that LabRecorderCLI has exited, keeping the time of an earlier stop.
+    fn mark_exited(&self) {
+        *lock(&self.state) = RecorderState::Exited;
+        lock(&self.stopped_at).get_or_insert_with(Instant::now);
+    }
+
+    /// Take the file that was just closed, once, to hash and journal it with
+    /// `Closing::finish`. `None` if there is nothing to write for it.
+    fn close_file(
+        &self,
+        exit_code: Option<i32>,
+        stop_reason: Option<StopReason>,
+        clean: bool,
+    ) -> Option<Closing> {
+        let file = lock(&self.open_file).take()?;
+        let checksums = clean && self.launch.checksums;
+        if !checksums && self.launch.journal.is_none() {
+            return None;
+        }
+        Some(Closing {
+            file,
+            stopped_at: SystemTime::now(),
+            streams: lock(&self.monitor).streams(),
+            stop_reason,
+            exit_code,
+            checksums,
+            journal: self.launch.journal.clone(),
+            predicates: self.launch.predicates.clone(),
+        })
+    }
+
+    /// Hash and journal a file closed by `split()` without holding up the next run.
+    fn finish_in_background(&self, closing: Option<Closing>) {
+        if let Some(closing) = closing {
+            let thread = std::thread::spawn(move || closing.finish());
+            let mut threads = lock(&self.closing);
+            threads.retain(|thread| !thread.is_finished());
+            threads.push(thread);
+        }
+    }
+
+    /// Hash and journal the last file, after the files closed before it.
+    fn finish_closing(&self, closing: Option<Closing>) {
+        for thread in std::mem::take(&mut *lock(&self.closing)) {
+            let _ = thread.join();
+        }
+        if let Some(closing) = closing {
+            closing.finish();
+        }
+    }
+}
+
+/// A closed file that still needs its checksum sidecars and journal entry.
+struct Closing {
+    file: OpenFile,
+    stopped_at: SystemTime,
+    streams: Vec<StreamStatus>,
+    stop_reason: Option<StopReason>,
+    exit_code: Option<i32>,
+    /// Write sidecars; only for files that were finished cleanly.
+    checksums: bool,
+    journal: Option<PathBuf>,
+    predicates: Vec<String>,
+}
+
+impl Closing {
+    fn finish(self) {
+        let sha256 = match sha256_file(&self.file.filename) {
+            Ok(sha256) => Some(sha256),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                eprintln!("Failed to hash {}: {}", self.file.filename.display(), e);
+                None
+            }
+        };
+        if self.checksums
+            && let Some(sha256) = &sha256
+            && let Err(e) = integrity::write_sidecars(&self.file.filename, sha256, &self.streams)
+        {
+            eprintln!(
+                "Failed to write checksum of {}: {}",
+                self.file.filename.display(),
+                e
+            );
+        }
+
+        if let Some(journal) = &self.journal {
+            let closed = ClosedFile {
+                file: self.file,
+                stopped_at: self.stopped_at,
+                predicates: &self.predicates,
+                streams: &self.streams,
+                stop_reason: self.stop_reason,
+                exit_code: self.exit_code,
+                sha256,
+            };
+            if let Err(e) = journal::append(journal, &closed) {
+                eprintln!("Failed to write journal {}: {}", journal.display(), e);
+            }
+        }
+    }
+}
+
 #[pyclass]
 #[derive(Clone)]
 pub struct LSLStreamRecorder {
-    process: Arc<Mutex<Child>>,
+    shared: Arc<Shared>,
 }
 
 impl LSLStreamRecorder {
@@ -25,105 +285,698 @@ impl LSLStreamRecorder {
         timeout: std::time::Duration,
         cli_path: Option<&str>,
     ) -> Result<Self, std::io::Error> {
-        // the recorder cli is in app/LabRecorderCLI
-
-        let cli_path = cli_path.unwrap_or("app/LabRecorderCLI");
-        let cli_path = std::path::Path::new(cli_path);
-
-        let mut command = std::process::Command::new(cli_path);
-
-        // wrap searchstring in single quotes
-        let seearchstring = format!("'{}'", seearchstring);
-
-        // run the command
-        // hide stdout and stderr
-        let mut child = command
-            .arg(filename)
-            .arg(seearchstring)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .stdin(std::process::Stdio::piped())
-            .spawn()
-            .expect("Failed to start LabRecorderCLI");
-
-        // read stdout until we find "Started data collection for stream"
-        let start_time = std::time::Instant::now();
-        while child.try_wait().is_ok() {
-            // read stdout
-            let mut buffer = String::new();
-            if let Some(ref mut stdout) = child.stdout {
-                let mut reader = std::io::BufReader::new(stdout);
-                if reader.read_line(&mut buffer).is_ok() {
-                    if buffer.contains("Started data collection for stream") {
-                        break;
-                    } else if buffer.contains("matched no stream!") {
+        Self::with_options(
+            filename,
+            seearchstring,
+            timeout,
+            cli_path,
+            RecorderOptions::default(),
+        )
+    }
+
+    pub fn with_options(
+        filename: &str,
+        seearchstring: &str,
+        timeout: std::time::Duration,
+        cli_path: Option<&str>,
+        options: RecorderOptions,
+    ) -> Result<Self, std::io::Error> {
+        Self::with_predicates(
+            filename,
+            &[seearchstring.to_string()],
+            timeout,
+            cli_path,
+            options,
+        )
+    }
+
+    /// Start a recording from the settings in a `LabRecorder.cfg`.
+    ///
+    /// The file name is built from the storage location or BIDS template with the
+    /// placeholders taken from `overrides`, and every `RequiredStreams` entry is
+    /// recorded along with the extra predicates.
+    pub fn from_config(path: &Path, overrides: &ConfigOverrides) -> Result<Self, std::io::Error> {
+        let config = LabRecorderConfig::load(path)?;
+        let filename = config.filename(&overrides.filename)?;
+        let mut predicates = config.required_predicates();
+        predicates.extend(overrides.predicates.iter().cloned());
+        if predicates.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} has no RequiredStreams and no other streams were given",
+                    path.display()
+                ),
+            ));
+        }
+        if let Some(parent) = filename.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Self::with_predicates(
+            &filename.to_string_lossy(),
+            &predicates,
+            overrides.timeout.unwrap_or(DEFAULT_START_TIMEOUT),
+            overrides.cli_path.as_deref().and_then(Path::to_str),
+            overrides.options.clone(),
+        )
+    }
+
+    /// Record all streams matching any of `predicates` into one file.
+    pub fn with_predicates(
+        filename: &str,
+        predicates: &[String],
+        timeout: std::time::Duration,
+        cli_path: Option<&str>,
+        options: RecorderOptions,
+    ) -> Result<Self, std::io::Error> {
+        // liblsl reads its config once per process, so the outlets below would
+        // silently use different settings than LabRecorderCLI
+        if options.network.is_some()
+            && (options.marker_stream.is_some() || !options.metadata.is_empty())
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "network cannot be combined with marker_stream or metadata, the in-process \
+                 outlets would not use the network settings (configure them in the \
+                 lsl_api.cfg of this machine instead)",
+            ));
+        }
+        let cli_path = discovery::find_cli(cli_path.map(Path::new), None)?;
+
+        let mut predicates = predicates.to_vec();
+        let markers = options
+            .marker_stream
+            .as_deref()
+            .map(|name| StringOutlet::new(name, "Markers", &BTreeMap::new(), cli_path.parent()))
+            .transpose()?;
+        if let Some(markers) = &markers {
+            predicates.push(markers.predicate());
+        }
+        let session_info = if options.metadata.is_empty() {
+            None
+        } else {
+            let outlet = StringOutlet::new(
+                SESSION_INFO_STREAM,
+                SESSION_INFO_STREAM,
+                &options.metadata,
+                cli_path.parent(),
+            )?;
+            predicates.push(outlet.predicate());
+            // readers may skip streams without samples, so the values also go into one
+            let sample = options
+                .metadata
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Some((outlet, sample))
+        };
+
+        let launch = Launch {
+            cli_path,
+            predicates,
+            timeout,
+            lsl_config: options
+                .network
+                .as_ref()
+                .map(NetworkConfig::write_temp)
+                .transpose()?,
+            markers,
+            session_info,
+            journal: options.journal.clone(),
+            checksums: options.checksums,
+        };
+        let (child, monitor) = spawn_cli(&launch, Path::new(filename))?;
+
+        let recorder = LSLStreamRecorder {
+            shared: Arc::new(Shared {
+                process: Mutex::new(child),
+                monitor: Mutex::new(monitor),
+                launch,
+                files: Mutex::new(vec![PathBuf::from(filename)]),
+                open_file: Mutex::new(Some(OpenFile {
+                    filename: PathBuf::from(filename),
+                    started_at: SystemTime::now(),
+                })),
+                closing: Mutex::new(Vec::new()),
+                state: Mutex::new(RecorderState::Recording),
+                started_at: Instant::now(),
+                stopped_at: Mutex::new(None),
+                stop_reason: Mutex::new(None),
+                events: EventLog::default(),
+                done: Arc::new(AtomicBool::new(false)),
+            }),
+        };
+        recorder.shared.events.push(RecorderEvent::Started);
+        if let Ok(mut recorders) = RECORDERS.lock() {
+            recorders.retain(|recorder| recorder.strong_count() > 0);
+            recorders.push(Arc::downgrade(&recorder.shared));
+        }
+
+        // stop the recording from a background thread when a guard fires
+        if !options.guards.is_empty() {
+            let watched = recorder.downgrade();
+            let current = recorder.downgrade();
+            guard::spawn_watchdog(
+                options.guards,
+                move || {
+                    current
+                        .upgrade()
+                        .map(|shared| LSLStreamRecorder { shared }.filename())
+                        .unwrap_or_default()
+                },
+                recorder.shared.done.clone(),
+                move |reason| {
+                    let Some(shared) = watched.upgrade() else {
+                        return;
+                    };
+                    let watched = LSLStreamRecorder { shared };
+                    watched
+                        .shared
+                        .events
+                        .push(RecorderEvent::GuardTriggered(reason));
+                    if let Err(e) = watched.stop_with_reason(reason, DEFAULT_STOP_TIMEOUT) {
+                        eprintln!("Failed to stop recorder after {}: {}", reason, e);
                     }
-                }
-            }
+                },
+            );
+        }
+
+        if let Some(interval) = options.split_every {
+            let rotated = recorder.downgrade();
+            rotation::spawn_rotation(
+                interval,
+                recorder.shared.done.clone(),
+                move || match rotated.upgrade() {
+                    Some(shared) => LSLStreamRecorder { shared }.split_unless_stopped(),
+                    None => Ok(None),
+                },
+            );
+        }
+
+        Ok(recorder)
+    }
+
+    /// Stop the recording, waiting up to `timeout` for the file to be finalized.
+    ///
+    /// A recorder that does not exit in time is terminated and then killed. It is
+    /// safe to call this more than once; later calls report `AlreadyExited`.
+    pub fn stop(&self, timeout: Duration) -> Result<StopOutcome, std::io::Error> {
+        self.stop_with_reason(StopReason::Requested, timeout)
+    }
+
+    /// Close the current file and continue recording into the next numbered run.
+    ///
+    /// Returns the gap between the end of the previous file and the start of data
+    /// collection in the new one.
+    pub fn split(&self) -> Result<Duration, std::io::Error> {
+        self.split_unless_stopped()?
+            .ok_or_else(|| std::io::Error::other("Recording has already stopped"))
+    }
 
-            // check for timeout
-            if start_time.elapsed() > timeout {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::TimedOut,
-                    "Timeout waiting for LSL stream to start",
-                ));
+    /// `split()`, or `None` if `stop()` got there first.
+    ///
+    /// The rotation thread can race a `stop()`; checking under the process lock
+    /// lets it end quietly instead of reporting a failed split.
+    fn split_unless_stopped(&self) -> Result<Option<Duration>, std::io::Error> {
+        let mut process = lock(&self.shared.process);
+        if self.shared.done.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        if process.try_wait()?.is_some() {
+            return Err(std::io::Error::other("Recording has already stopped"));
+        }
+
+        // status() reads the files, so they are not locked while the CLI restarts
+        let next = {
+            let files = lock(&self.shared.files);
+            rotation::next_run_filename(&files[0], files.len() + 1)
+        };
+
+        *lock(&self.shared.state) = RecorderState::Starting;
+        mark_stopped(&self.shared.launch);
+        let outcome = lifetime::shutdown(&mut process, DEFAULT_STOP_TIMEOUT)?;
+        let stopped_at = Instant::now();
+        let closing = self.shared.close_file(
+            exit_code(&mut process),
+            Some(StopReason::Split),
+            outcome == StopOutcome::Clean,
+        );
+        let spawned = spawn_cli(&self.shared.launch, &next);
+        // hashing a large file would delay the next run's data, so it happens afterwards
+        self.shared.finish_in_background(closing);
+        match spawned {
+            Ok((child, monitor)) => {
+                *process = child;
+                *lock(&self.shared.monitor) = monitor;
+                *lock(&self.shared.open_file) = Some(OpenFile {
+                    filename: next.clone(),
+                    started_at: SystemTime::now(),
+                });
+            }
+            Err(e) => {
+                self.shared.done.store(true, Ordering::Relaxed);
+                *lock(&self.shared.state) = RecorderState::Exited;
+                *lock(&self.shared.stopped_at) = Some(stopped_at);
+                return Err(e);
             }
         }
+        let gap = stopped_at.elapsed();
+        *lock(&self.shared.state) = RecorderState::Recording;
 
-        // return LSLStreamRecorder
-        Ok(LSLStreamRecorder {
-            process: Arc::new(Mutex::new(child)),
-        })
+        lock(&self.shared.files).push(next.clone());
+        self.shared.events.push(RecorderEvent::Split {
+            filename: next,
+            gap,
+        });
+        Ok(Some(gap))
+    }
+
+    /// The file currently being recorded to.
+    pub fn filename(&self) -> PathBuf {
+        lock(&self.shared.files).last().cloned().unwrap_or_default()
+    }
+
+    /// All files written so far, one per run.
+    pub fn files(&self) -> Vec<PathBuf> {
+        lock(&self.shared.files).clone()
+    }
+
+    /// Current state, progress and per-stream status of the recording.
+    ///
+    /// This never waits for a `stop()` or `split()` in progress. A recorder that
+    /// died on its own is reported as `Exited` with its exit code and no stop reason.
+    pub fn status(&self) -> RecorderStatus {
+        let exit_code = self.shared.poll_exit().flatten();
+        let state = *lock(&self.shared.state);
+
+        let files = self.files();
+        let end = lock(&self.shared.stopped_at).unwrap_or_else(Instant::now);
+        RecorderStatus {
+            state,
+            elapsed: end.duration_since(self.shared.started_at),
+            streams: lock(&self.shared.monitor).streams(),
+            bytes_written: files
+                .iter()
+                .filter_map(|file| std::fs::metadata(file).ok())
+                .map(|metadata| metadata.len())
+                .sum(),
+            exit_code,
+            stop_reason: self.stop_reason(),
+            filename: files.last().cloned().unwrap_or_default(),
+            next_start: None,
+        }
+    }
+
+    /// Why the recording ended, or `None` while it is still running.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        *lock(&self.shared.stop_reason)
+    }
+
+    /// Push a marker into the recording's marker stream.
+    ///
+    /// `timestamp` is in seconds on the LSL clock and defaults to now. Returns the
+    /// timestamp of the marker. Requires `RecorderOptions::marker_stream`.
+    pub fn annotate(&self, marker: &str, timestamp: Option<f64>) -> Result<f64, std::io::Error> {
+        let Some(markers) = &self.shared.launch.markers else {
+            return Err(std::io::Error::other(
+                "Recorder has no marker stream, create it with marker_stream",
+            ));
+        };
+        if self.shared.poll_exit().is_some() || *lock(&self.shared.state) == RecorderState::Exited {
+            return Err(std::io::Error::other("Recording has already stopped"));
+        }
+        markers.push(marker, timestamp)
+    }
+
+    /// Remove and return the events that happened since the last call.
+    pub fn take_events(&self) -> Vec<(std::time::SystemTime, RecorderEvent)> {
+        self.shared.events.take()
+    }
+
+    fn downgrade(&self) -> Weak<Shared> {
+        Arc::downgrade(&self.shared)
+    }
+
+    fn stop_with_reason(
+        &self,
+        reason: StopReason,
+        timeout: Duration,
+    ) -> Result<StopOutcome, std::io::Error> {
+        let mut process = lock(&self.shared.process);
+        self.shared.done.store(true, Ordering::Relaxed);
+        // a guard or an earlier call may already have stopped the recording
+        if let Some(status) = process.try_wait()? {
+            lifetime::unregister(&process);
+            self.shared.mark_exited();
+            let closing = self
+                .shared
+                .close_file(status.code(), self.stop_reason(), false);
+            drop(process);
+            self.shared.finish_closing(closing);
+            return Ok(StopOutcome::AlreadyExited(status.code()));
+        }
+        *lock(&self.shared.stop_reason) = Some(reason);
+        *lock(&self.shared.state) = RecorderState::Stopping;
+
+        mark_stopped(&self.shared.launch);
+        let result = lifetime::shutdown(&mut process, timeout);
+        *lock(&self.shared.state) = RecorderState::Exited;
+        *lock(&self.shared.stopped_at) = Some(Instant::now());
+        let closing =
+            self.shared
+                .close_file(exit_code(&mut process), Some(reason), is_clean(&result));
+        // status() and annotate() can see the exit while the file is hashed
+        drop(process);
+        self.shared.finish_closing(closing);
+        let outcome = result?;
+        self.shared.events.push(RecorderEvent::Stopped(reason));
+        Ok(outcome)
+    }
+}
+
+/// Stop every recorder that is still running in this process.
+///
+/// Registered with `atexit` by the Python package, so a crashing experiment
+/// script does not leave LabRecorderCLI running.
+pub fn stop_all() {
+    let recorders = match RECORDERS.lock() {
+        Ok(mut recorders) => std::mem::take(&mut *recorders),
+        Err(_) => return,
+    };
+    for shared in recorders.iter().filter_map(Weak::upgrade) {
+        let recorder = LSLStreamRecorder { shared };
+        if let Err(e) = recorder.stop_with_reason(StopReason::Requested, DEFAULT_STOP_TIMEOUT) {
+            eprintln!("Failed to stop recorder: {}", e);
+        }
+    }
+}
+
+/// Start LabRecorderCLI writing to `filename` and wait until data collection has started.
+fn spawn_cli(launch: &Launch, filename: &Path) -> Result<(Child, Arc<Monitor>), std::io::Error> {
+    let mut command = std::process::Command::new(&launch.cli_path);
+
+    // wrap each searchstring in single quotes
+    let predicates = launch
+        .predicates
+        .iter()
+        .map(|predicate| format!("'{}'", predicate));
+    let timeout = launch.timeout;
+
+    // run the command
+    // hide stdout and stderr
+    command
+        .arg(filename)
+        .args(predicates)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .stdin(std::process::Stdio::piped());
+    if let Some(lsl_config) = &launch.lsl_config {
+        command.env(LSL_CONFIG_ENV_VAR, lsl_config.path());
+    }
+    let mut child = lifetime::spawn(command)?;
+    lifetime::register(&child, filename);
+
+    // follow stdout until we find "Started data collection for stream"
+    let monitor = Monitor::attach(&mut child);
+    if let Err(e) = monitor.wait_started(timeout) {
+        let _ = lifetime::shutdown(&mut child, Duration::ZERO);
+        return Err(e);
+    }
+
+    // samples pushed before LabRecorderCLI subscribed to our own streams would be lost
+    let first_samples = launch
+        .markers
+        .as_ref()
+        .map(|markers| (markers, START_MARKER))
+        .into_iter()
+        .chain(
+            launch
+                .session_info
+                .as_ref()
+                .map(|(outlet, sample)| (outlet, sample.as_str())),
+        );
+    for (outlet, sample) in first_samples {
+        let pushed = if outlet.wait_for_consumers(timeout) {
+            outlet.push(sample, None).map(|_| ())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Timeout waiting for LabRecorderCLI to record the recorder's own streams",
+            ))
+        };
+        if let Err(e) = pushed {
+            let _ = lifetime::shutdown(&mut child, Duration::ZERO);
+            return Err(e);
+        }
     }
 
-    pub fn stop(&mut self) -> Result<(), std::io::Error> {
-        // send enter key to the process
-        // this will stop the recording
-        self.process
-            .lock()
-            .unwrap()
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(b"\n")?;
-        // wait for process to finish
-        self.process.lock().unwrap().wait()?;
-        Ok(())
+    Ok((child, monitor))
+}
+
+/// Push the stop marker and give LabRecorderCLI time to receive it.
+fn mark_stopped(launch: &Launch) {
+    if let Some(markers) = &launch.markers {
+        match markers.push(STOP_MARKER, None) {
+            Ok(_) => std::thread::sleep(MARKER_FLUSH_DELAY),
+            Err(e) => eprintln!("Failed to push stop marker: {}", e),
+        }
     }
 }
 
+/// Exit code of a child that has exited, `None` if it is running or was killed by a signal.
+fn exit_code(child: &mut Child) -> Option<i32> {
+    child
+        .try_wait()
+        .ok()
+        .flatten()
+        .and_then(|status| status.code())
+}
+
+fn is_clean(result: &Result<StopOutcome, std::io::Error>) -> bool {
+    matches!(result, Ok(StopOutcome::Clean))
+}
+
+/// Lock a mutex, recovering the data if another thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 #[pymethods]
 impl LSLStreamRecorder {
     /// Create a new LSLStreamRecorder.
+    ///
+    /// `max_duration` (seconds), `max_file_size` (bytes) and `min_free_disk` (bytes)
+    /// stop the recording automatically when exceeded; with `split_every`
+    /// (seconds), which continues the recording in a new numbered run at that
+    /// interval, `max_file_size` applies to the file of the current run.
+    ///
+    /// `cli_path` overrides the LabRecorderCLI location, otherwise the
+    /// `LSL_RECORDER_CLI` environment variable, `PATH` and the bundled binary are
+    /// tried in that order. `network` passes liblsl network settings (a
+    /// `NetworkConfig`) to LabRecorderCLI; it cannot be combined with
+    /// `marker_stream` or `metadata`.
+    ///
+    /// `marker_stream` names a marker stream that is recorded along with the other
+    /// streams and receives the start and stop markers and `annotate()` calls.
+    /// `metadata` (a dict of str) is stored in the header of a "SessionInfo" stream.
+    /// `journal` is a JSON-lines file that gets an entry for every finished file.
+    /// With `checksums`, every cleanly finished file gets `.sha256` and `.json`
+    /// sidecars that `verify_integrity()` checks.
     #[new]
-    fn py_new(filename: String, seearchstring: String, timeout: f64, py: Python) -> PyResult<Self> {
-        let timeout = std::time::Duration::from_secs_f64(timeout);
-        // read path of the package
-        let module = PyModule::import(py, "lsl_recorder")?;
-        let path = module.getattr("__file__")?.extract::<String>()?;
-        // get the path of the package
-        let path = std::path::Path::new(&path);
-        // get the path of the cli
-        let cli_path = path.parent().unwrap();
-        // get the path of the cli
-        let cli_path = cli_path.join("app");
-        let cli_path = cli_path.join("LabRecorderCLI");
-        // convert to string
-        let cli_path = cli_path.to_str().unwrap();
-
-        let recorder = LSLStreamRecorder::new(&filename, &seearchstring, timeout, Some(cli_path))
-            .map_err(|e| {
-            PyRuntimeError::new_err(format!("Failed to create recorder: {}", e))
-        })?;
+    #[pyo3(signature = (filename, seearchstring, timeout, max_duration = None, max_file_size = None, min_free_disk = None, split_every = None, cli_path = None, network = None, marker_stream = None, metadata = None, journal = None, checksums = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        filename: String,
+        seearchstring: String,
+        timeout: f64,
+        max_duration: Option<f64>,
+        max_file_size: Option<u64>,
+        min_free_disk: Option<u64>,
+        split_every: Option<f64>,
+        cli_path: Option<String>,
+        network: Option<NetworkConfig>,
+        marker_stream: Option<String>,
+        metadata: Option<BTreeMap<String, String>>,
+        journal: Option<PathBuf>,
+        checksums: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let timeout = py_duration("timeout", timeout)?;
+        let options = RecorderOptions {
+            guards: RecordingGuards {
+                max_duration: max_duration
+                    .map(|seconds| py_duration("max_duration", seconds))
+                    .transpose()?,
+                max_file_size,
+                min_free_disk,
+            },
+            split_every: split_every
+                .map(|seconds| py_duration("split_every", seconds))
+                .transpose()?,
+            network,
+            marker_stream,
+            metadata: metadata.unwrap_or_default(),
+            journal,
+            checksums,
+        };
+        let cli_path = py_find_cli(cli_path, py)?;
+        let cli_path = cli_path.to_string_lossy();
+
+        // waiting for the streams can take up to `timeout`, don't block other Python threads
+        let recorder = py
+            .allow_threads(|| {
+                LSLStreamRecorder::with_options(
+                    &filename,
+                    &seearchstring,
+                    timeout,
+                    Some(&cli_path),
+                    options,
+                )
+            })
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to create recorder: {}", e)))?;
         Ok(recorder)
     }
 
-    /// Stop the recording.
+    /// Start a recording from the settings in a `LabRecorder.cfg`.
+    ///
+    /// The placeholders of the storage location or BIDS template are filled in from
+    /// `participant`, `session`, `block`, `acquisition`, `modality` and `run`;
+    /// `root` and `template` replace `StudyRoot` and `PathTemplate`. The
+    /// `RequiredStreams` are recorded together with any extra `predicates`.
+    #[staticmethod]
+    #[pyo3(name = "from_config")]
+    #[pyo3(signature = (path, participant = None, session = None, block = None, acquisition = None, modality = None, run = None, root = None, template = None, predicates = Vec::new(), timeout = DEFAULT_START_TIMEOUT.as_secs_f64(), cli_path = None, network = None, marker_stream = None, metadata = None, journal = None, checksums = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_from_config(
+        path: String,
+        participant: Option<String>,
+        session: Option<String>,
+        block: Option<String>,
+        acquisition: Option<String>,
+        modality: Option<String>,
+        run: Option<u32>,
+        root: Option<String>,
+        template: Option<String>,
+        predicates: Vec<String>,
+        timeout: f64,
+        cli_path: Option<String>,
+        network: Option<NetworkConfig>,
+        marker_stream: Option<String>,
+        metadata: Option<BTreeMap<String, String>>,
+        journal: Option<PathBuf>,
+        checksums: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let overrides = ConfigOverrides {
+            filename: RcsFilename {
+                root,
+                template,
+                participant,
+                session,
+                task: block,
+                acquisition,
+                modality,
+                run,
+            },
+            predicates,
+            timeout: Some(py_duration("timeout", timeout)?),
+            cli_path: Some(py_find_cli(cli_path, py)?),
+            options: RecorderOptions {
+                network,
+                marker_stream,
+                metadata: metadata.unwrap_or_default(),
+                journal,
+                checksums,
+                ..RecorderOptions::default()
+            },
+        };
+        py.allow_threads(|| LSLStreamRecorder::from_config(Path::new(&path), &overrides))
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to create recorder: {}", e)))
+    }
+
+    /// Stop the recording, waiting up to `timeout` seconds for the file to be finalized.
+    ///
+    /// Returns an `(outcome, exit_code)` tuple. The outcome is "clean", "forced" (the
+    /// recorder had to be terminated or killed) or "already_exited", in which case
+    /// `exit_code` is the exit code of LabRecorderCLI if it has one, otherwise None.
     #[pyo3(name = "stop")]
-    fn py_stop(&mut self) -> PyResult<()> {
-        self.stop()
+    #[pyo3(signature = (timeout = DEFAULT_STOP_TIMEOUT.as_secs_f64()))]
+    fn py_stop(&self, timeout: f64, py: Python) -> PyResult<(String, Option<i32>)> {
+        let timeout = py_duration("timeout", timeout)?;
+        let outcome = py
+            .allow_threads(|| self.stop(timeout))
             .map_err(|e| PyRuntimeError::new_err(format!("Failed to stop recorder: {}", e)))?;
-        Ok(())
+        let exit_code = match outcome {
+            StopOutcome::AlreadyExited(code) => code,
+            _ => None,
+        };
+        Ok((outcome.to_string(), exit_code))
+    }
+
+    /// Current state, progress and per-stream status of the recording.
+    #[pyo3(name = "status")]
+    fn py_status(&self) -> RecorderStatus {
+        self.status()
+    }
+
+    /// Close the current file and continue in the next numbered run.
+    ///
+    /// Returns the gap in seconds between the two files.
+    #[pyo3(name = "split")]
+    fn py_split(&self, py: Python) -> PyResult<f64> {
+        py.allow_threads(|| self.split())
+            .map(|gap| gap.as_secs_f64())
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to split recording: {}", e)))
+    }
+
+    /// The file currently being recorded to.
+    #[getter(filename)]
+    fn py_filename(&self) -> String {
+        self.filename().to_string_lossy().into_owned()
+    }
+
+    /// All files written so far, one per run.
+    #[getter(files)]
+    fn py_files(&self) -> Vec<String> {
+        self.files()
+            .iter()
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Why the recording ended ("requested", "max_duration", "max_file_size",
+    /// "low_disk_space", "scheduled"), or None while it is still running.
+    #[getter(stop_reason)]
+    fn py_stop_reason(&self) -> Option<String> {
+        self.stop_reason().map(|reason| reason.to_string())
+    }
+
+    /// Push a marker into the marker stream, see `marker_stream`.
+    ///
+    /// `timestamp` is in seconds on the LSL clock and defaults to now. Returns the
+    /// timestamp of the marker.
+    #[pyo3(name = "annotate")]
+    #[pyo3(signature = (marker, timestamp = None))]
+    fn py_annotate(&self, marker: &str, timestamp: Option<f64>) -> PyResult<f64> {
+        self.annotate(marker, timestamp)
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to annotate recording: {}", e)))
+    }
+
+    /// Return the events since the last call as (unix time, event) tuples.
+    #[pyo3(name = "take_events")]
+    fn py_take_events(&self) -> Vec<(f64, String)> {
+        self.take_events()
+            .into_iter()
+            .map(|(time, event)| {
+                let time = time
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs_f64())
+                    .unwrap_or_default();
+                (time, event.to_string())
+            })
+            .collect()
     }
 
     fn __enter__(slf: PyRef<Self>) -> PyResult<Py<Self>> {
@@ -132,19 +985,97 @@ impl LSLStreamRecorder {
     }
 
     fn __exit__(
-        mut slf: PyRefMut<Self>,
+        slf: PyRef<Self>,
         exc_type: Bound<'_, PyAny>,
-        exc_value: Bound<'_, PyAny>,
-        traceback: Bound<'_, PyAny>,
+        _exc_value: Bound<'_, PyAny>,
+        _traceback: Bound<'_, PyAny>,
     ) -> PyResult<()> {
         // stop the recorder
-        slf.py_stop()?;
-        Ok(())
+        let py = slf.py();
+        let result = slf.py_stop(DEFAULT_STOP_TIMEOUT.as_secs_f64(), py);
+        // don't replace an exception that is already propagating
+        match result {
+            Err(e) if !exc_type.is_none() => {
+                eprintln!("{}", e);
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
     }
 }
 
+/// Stop every recorder that is still running in this process.
+#[pyfunction(name = "stop_all")]
+fn py_stop_all(py: Python) {
+    py.allow_threads(stop_all);
+}
+
+/// Locate LabRecorderCLI, trying `cli_path`, the `LSL_RECORDER_CLI` environment
+/// variable, `PATH` and the binary bundled with this package in that order.
+#[pyfunction(name = "find_cli")]
+#[pyo3(signature = (cli_path = None))]
+fn py_find_cli(cli_path: Option<String>, py: Python) -> PyResult<PathBuf> {
+    // read path of the package
+    let module = PyModule::import(py, "lsl_recorder")?;
+    let path = module.getattr("__file__")?.extract::<String>()?;
+    // the bundled cli is in <package>/app
+    let bundled_dir = Path::new(&path).parent().map(|path| path.join("app"));
+
+    find_cli(cli_path.as_deref().map(Path::new), bundled_dir.as_deref())
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to find LabRecorderCLI: {}", e)))
+}
+
+/// `seconds` from Python as a `Duration`, rejecting negative, infinite and NaN
+/// values, which `Duration::from_secs_f64` panics on.
+pub(crate) fn py_duration(name: &str, seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| {
+        PyValueError::new_err(format!(
+            "{} must be a non-negative number of seconds, not {}",
+            name, seconds
+        ))
+    })
+}
+
+/// Check that LabRecorderCLI exists, is executable and is LabRecorderCLI.
+#[pyfunction(name = "check_cli")]
+#[pyo3(signature = (cli_path = None))]
+fn py_check_cli(cli_path: Option<String>, py: Python) -> PyResult<CliInfo> {
+    let cli_path = py_find_cli(cli_path, py)?;
+    py.allow_threads(|| check_cli(&cli_path))
+        .map_err(|e| PyRuntimeError::new_err(format!("LabRecorderCLI preflight failed: {}", e)))
+}
+
+/// Check a recording against its `.sha256` and `.json` sidecars.
+#[pyfunction(name = "verify_integrity")]
+fn py_verify_integrity(path: PathBuf, py: Python) -> PyResult<IntegrityReport> {
+    py.allow_threads(|| verify_integrity(&path))
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to verify {}: {}", path.display(), e)))
+}
+
+/// Find LabRecorderCLI processes left behind by a program that has exited.
+#[pyfunction(name = "leftover_processes")]
+fn py_leftover_processes() -> Vec<LeftoverProcess> {
+    leftover_processes()
+}
+
 #[pymodule]
 fn lsl_recorder(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<LSLStreamRecorder>()?;
+    m.add_class::<RcsClient>()?;
+    m.add_class::<LeftoverProcess>()?;
+    m.add_class::<RecorderStatus>()?;
+    m.add_class::<RecorderState>()?;
+    m.add_class::<StreamStatus>()?;
+    m.add_class::<StreamState>()?;
+    m.add_function(wrap_pyfunction!(py_stop_all, m)?)?;
+    m.add_function(wrap_pyfunction!(py_leftover_processes, m)?)?;
+    m.add_function(wrap_pyfunction!(py_find_cli, m)?)?;
+    m.add_function(wrap_pyfunction!(py_check_cli, m)?)?;
+    m.add_class::<CliInfo>()?;
+    m.add_class::<NetworkConfig>()?;
+    m.add_class::<LabRecorderConfig>()?;
+    m.add_class::<ScheduledRecorder>()?;
+    m.add_class::<IntegrityReport>()?;
+    m.add_function(wrap_pyfunction!(py_verify_integrity, m)?)?;
     Ok(())
 }
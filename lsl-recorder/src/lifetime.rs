@@ -0,0 +1,295 @@
+//! Keeping LabRecorderCLI from outliving the process that started it.
+//!
+//! Every running child is registered with a small pid file in the temp directory,
+//! so children left behind by a crashed or killed owner can be found later.
+
+use std::{
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    time::{Duration, Instant},
+};
+
+use pyo3::{PyResult, exceptions::PyRuntimeError, pyclass, pymethods};
+
+/// A LabRecorderCLI process whose owner is no longer running.
+#[pyclass(get_all)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeftoverProcess {
+    /// Process id of LabRecorderCLI.
+    pub pid: u32,
+    /// Process id of the program that started it.
+    pub owner_pid: u32,
+    /// File the process is (or was) recording to.
+    pub filename: String,
+}
+
+impl LeftoverProcess {
+    /// Terminate the leftover process.
+    pub fn kill(&self) -> Result<(), std::io::Error> {
+        terminate(self.pid)?;
+        let _ = std::fs::remove_file(pid_file(self.pid));
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl LeftoverProcess {
+    /// Terminate the leftover process.
+    #[pyo3(name = "kill")]
+    fn py_kill(&self) -> PyResult<()> {
+        self.kill().map_err(|e| {
+            PyRuntimeError::new_err(format!("Failed to kill process {}: {}", self.pid, e))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "LeftoverProcess(pid={}, owner_pid={}, filename='{}')",
+            self.pid, self.owner_pid, self.filename
+        )
+    }
+}
+
+/// Find LabRecorderCLI processes started by a program that has since exited.
+///
+/// Stale pid files of processes that are no longer running are removed.
+pub fn leftover_processes() -> Vec<LeftoverProcess> {
+    let Ok(entries) = std::fs::read_dir(pid_dir()) else {
+        return Vec::new();
+    };
+
+    let mut leftovers = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(leftover) = read_pid_file(&path) else {
+            continue;
+        };
+        if !is_alive(leftover.pid) {
+            let _ = std::fs::remove_file(&path);
+        } else if leftover.owner_pid != std::process::id() && !is_alive(leftover.owner_pid) {
+            leftovers.push(leftover);
+        }
+    }
+    leftovers
+}
+
+/// Spawn `command` so that the child does not outlive this process.
+///
+/// On Linux the child receives SIGTERM when its parent dies. The kernel ties this
+/// to the spawning *thread*, so all children are spawned from one thread that
+/// lives as long as the process, rather than from short-lived executor threads.
+#[cfg(target_os = "linux")]
+pub(crate) fn spawn(mut command: Command) -> Result<Child, std::io::Error> {
+    use std::sync::{
+        Mutex, OnceLock,
+        mpsc::{Sender, channel},
+    };
+
+    type Request = (Command, Sender<Result<Child, std::io::Error>>);
+    static SPAWNER: OnceLock<Mutex<Sender<Request>>> = OnceLock::new();
+
+    die_with_parent(&mut command);
+    let spawner = SPAWNER.get_or_init(|| {
+        let (sender, receiver) = channel::<Request>();
+        std::thread::spawn(move || {
+            for (mut command, reply) in receiver {
+                let _ = reply.send(command.spawn());
+            }
+        });
+        Mutex::new(sender)
+    });
+
+    let (reply, response) = channel();
+    spawner
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .send((command, reply))
+        .map_err(|_| std::io::Error::other("Spawner thread has exited"))?;
+    response
+        .recv()
+        .map_err(|_| std::io::Error::other("Spawner thread has exited"))?
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn spawn(mut command: Command) -> Result<Child, std::io::Error> {
+    command.spawn()
+}
+
+/// Make the child receive SIGTERM when the thread that spawned it exits.
+#[cfg(target_os = "linux")]
+fn die_with_parent(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    let parent = std::process::id();
+    unsafe {
+        command.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // the parent may have exited before prctl took effect
+            if libc::getppid() as u32 != parent {
+                libc::raise(libc::SIGTERM);
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Record a freshly spawned child in its pid file.
+pub(crate) fn register(child: &Child, filename: &Path) {
+    let dir = pid_dir();
+    if std::fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    if let Ok(mut file) = std::fs::File::create(pid_file(child.id())) {
+        let _ = write!(
+            file,
+            "{}\n{}\n",
+            std::process::id(),
+            filename.to_string_lossy()
+        );
+    }
+}
+
+/// Remove the pid file of a child that has exited.
+pub(crate) fn unregister(child: &Child) {
+    let _ = std::fs::remove_file(pid_file(child.id()));
+}
+
+/// How long a terminated child gets to exit before it is killed.
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How a LabRecorderCLI process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// The process finished the file and exited after the stop command.
+    Clean,
+    /// The process did not exit in time and was terminated or killed.
+    Forced,
+    /// The process had already exited, with this exit code if it has one.
+    AlreadyExited(Option<i32>),
+}
+
+impl fmt::Display for StopOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopOutcome::Clean => write!(f, "clean"),
+            StopOutcome::Forced => write!(f, "forced"),
+            StopOutcome::AlreadyExited(_) => write!(f, "already_exited"),
+        }
+    }
+}
+
+/// Ask the child to finish its file and wait up to `timeout` for it to exit.
+///
+/// A child that does not exit in time is terminated and, if that does not help
+/// either, killed.
+pub(crate) fn shutdown(
+    child: &mut Child,
+    timeout: Duration,
+) -> Result<StopOutcome, std::io::Error> {
+    if let Some(status) = child.try_wait()? {
+        unregister(child);
+        return Ok(StopOutcome::AlreadyExited(status.code()));
+    }
+
+    // send enter key to the process, this will stop the recording
+    // (a broken pipe means the process is already on its way out)
+    if let Some(stdin) = child.stdin.as_mut() {
+        let _ = stdin.write_all(b"\n").and_then(|_| stdin.flush());
+    }
+
+    let outcome = if wait_timeout(child, timeout)? {
+        StopOutcome::Clean
+    } else {
+        terminate_child(child);
+        if !wait_timeout(child, TERMINATE_GRACE_PERIOD)? {
+            child.kill()?;
+            child.wait()?;
+        }
+        StopOutcome::Forced
+    };
+    unregister(child);
+    Ok(outcome)
+}
+
+/// Wait for the child to exit, returning `false` if it is still running after `timeout`.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<bool, std::io::Error> {
+    let start_time = Instant::now();
+    while child.try_wait()?.is_none() {
+        if start_time.elapsed() > timeout {
+            return Ok(false);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn terminate_child(child: &Child) {
+    let _ = terminate(child.id());
+}
+
+/// Windows has no SIGTERM, so the caller goes straight to `kill`.
+#[cfg(windows)]
+fn terminate_child(_child: &Child) {}
+
+fn pid_dir() -> PathBuf {
+    std::env::temp_dir().join("lsl-recorder")
+}
+
+fn pid_file(pid: u32) -> PathBuf {
+    pid_dir().join(format!("{}.pid", pid))
+}
+
+fn read_pid_file(path: &Path) -> Option<LeftoverProcess> {
+    let pid = path.file_stem()?.to_str()?.parse().ok()?;
+    let contents = std::fs::read_to_string(path).ok()?;
+    let mut lines = contents.lines();
+    let owner_pid = lines.next()?.parse().ok()?;
+    let filename = lines.next().unwrap_or_default().to_string();
+    Some(LeftoverProcess {
+        pid,
+        owner_pid,
+        filename,
+    })
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<(), std::io::Error> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn is_alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+        .unwrap_or(false)
+}
+
+#[cfg(windows)]
+fn terminate(pid: u32) -> Result<(), std::io::Error> {
+    let status = Command::new("taskkill")
+        .args(["/F", "/PID", &pid.to_string()])
+        .status()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "taskkill exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,273 @@
+//! Following the console output of LabRecorderCLI.
+//!
+//! stdout and stderr are drained on background threads, so the child never
+//! blocks on a full pipe, and the progress messages are parsed into a
+//! per-stream state.
+
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    process::Child,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::status::{StreamState, StreamStatus};
+
+/// Number of stderr lines kept for error messages.
+const STDERR_TAIL: usize = 10;
+
+#[derive(Default)]
+struct Output {
+    streams: Vec<StreamStatus>,
+    unmatched: Vec<String>,
+    stderr: VecDeque<String>,
+    started: bool,
+    closed: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Monitor {
+    output: Mutex<Output>,
+    changed: Condvar,
+}
+
+impl Monitor {
+    /// Take over the stdout and stderr pipes of `child`.
+    pub(crate) fn attach(child: &mut Child) -> Arc<Monitor> {
+        let monitor = Arc::new(Monitor::default());
+
+        if let Some(stdout) = child.stdout.take() {
+            let monitor = monitor.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    monitor.parse_line(&line);
+                }
+                monitor.lock().closed = true;
+                monitor.changed.notify_all();
+            });
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            let monitor = monitor.clone();
+            std::thread::spawn(move || monitor.drain_stderr(stderr));
+        }
+
+        monitor
+    }
+
+    /// Wait until LabRecorderCLI reports that data collection has started.
+    pub(crate) fn wait_started(&self, timeout: Duration) -> Result<(), std::io::Error> {
+        let deadline = Instant::now() + timeout;
+        let mut output = self.lock();
+        loop {
+            if output.started {
+                return Ok(());
+            }
+            if output.closed {
+                let mut message = String::from("LabRecorderCLI exited before recording started");
+                for predicate in &output.unmatched {
+                    message.push_str(&format!("; {} matched no stream", predicate));
+                }
+                if let Some(line) = output.stderr.back() {
+                    message.push_str(&format!("; {}", line));
+                }
+                return Err(std::io::Error::other(message));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "Timeout waiting for LSL stream to start",
+                ));
+            }
+            output = self
+                .changed
+                .wait_timeout(output, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    pub(crate) fn streams(&self) -> Vec<StreamStatus> {
+        self.lock().streams.clone()
+    }
+
+    fn parse_line(&self, line: &str) {
+        let line = line.trim();
+        let mut output = self.lock();
+
+        if let Some(predicate) = line.strip_suffix("matched no stream!") {
+            output.unmatched.push(predicate.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("Found ") {
+            // "Found <name>@<host> matching '<predicate>'"
+            let found = rest.split(" matching").next().unwrap_or(rest);
+            let (name, hostname) = found
+                .rsplit_once('@')
+                .map_or((found, None), |(name, hostname)| (name, Some(hostname)));
+            set_state(&mut output.streams, name, StreamState::Found).hostname =
+                hostname.map(str::to_string);
+        } else if let Some(name) = line.strip_prefix("Opened the stream ") {
+            set_state(&mut output.streams, name, StreamState::Opened);
+        } else if let Some(name) = line.strip_prefix("Received header for stream ") {
+            set_state(&mut output.streams, name, StreamState::HeaderReceived);
+        } else if let Some(name) = line.strip_prefix("Started data collection for stream ") {
+            set_state(&mut output.streams, name, StreamState::Collecting);
+            output.started = true;
+            self.changed.notify_all();
+        }
+    }
+
+    fn drain_stderr(&self, stderr: impl Read) {
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let mut output = self.lock();
+            if output.stderr.len() == STDERR_TAIL {
+                output.stderr.pop_front();
+            }
+            output.stderr.push_back(line);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Output> {
+        self.output.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn set_state<'a>(
+    streams: &'a mut Vec<StreamStatus>,
+    name: &str,
+    state: StreamState,
+) -> &'a mut StreamStatus {
+    let name = name.trim().trim_end_matches('.');
+    let index = match streams.iter().position(|stream| stream.name == name) {
+        Some(index) => index,
+        None => {
+            streams.push(StreamStatus {
+                name: name.to_string(),
+                hostname: None,
+                state,
+            });
+            streams.len() - 1
+        }
+    };
+    streams[index].state = state;
+    &mut streams[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(name: &str, hostname: Option<&str>, state: StreamState) -> StreamStatus {
+        StreamStatus {
+            name: name.to_string(),
+            hostname: hostname.map(str::to_string),
+            state,
+        }
+    }
+
+    #[test]
+    fn parses_cli_lines() {
+        use StreamState::*;
+
+        // line, streams, unmatched predicates, started
+        let cases: &[(&str, &[StreamStatus], &[&str], bool)] = &[
+            (
+                "Found EEG@lab-pc matching 'type=\"EEG\"'",
+                &[stream("EEG", Some("lab-pc"), Found)],
+                &[],
+                false,
+            ),
+            (
+                "Found Tobii@Pro@eye-pc matching 'name=\"Tobii@Pro\"'",
+                &[stream("Tobii@Pro", Some("eye-pc"), Found)],
+                &[],
+                false,
+            ),
+            (
+                "Found Markers",
+                &[stream("Markers", None, Found)],
+                &[],
+                false,
+            ),
+            (
+                "'type=\"Gaze\"' matched no stream!",
+                &[],
+                &["'type=\"Gaze\"'"],
+                false,
+            ),
+            (
+                "Opened the stream EEG.",
+                &[stream("EEG", None, Opened)],
+                &[],
+                false,
+            ),
+            (
+                "Received header for stream EEG.",
+                &[stream("EEG", None, HeaderReceived)],
+                &[],
+                false,
+            ),
+            (
+                "  Started data collection for stream EEG.\r",
+                &[stream("EEG", None, Collecting)],
+                &[],
+                true,
+            ),
+            ("Closing the file.", &[], &[], false),
+            ("", &[], &[], false),
+        ];
+
+        for (line, streams, unmatched, started) in cases {
+            let monitor = Monitor::default();
+            monitor.parse_line(line);
+            let output = monitor.lock();
+            assert_eq!(output.streams, *streams, "{}", line);
+            assert_eq!(output.unmatched, *unmatched, "{}", line);
+            assert_eq!(output.started, *started, "{}", line);
+        }
+    }
+
+    #[test]
+    fn follows_each_stream_through_the_recording() {
+        let monitor = Monitor::default();
+        for line in [
+            "Found EEG@lab-pc matching 'type=\"EEG\"'",
+            "Found Markers@stim-pc matching 'type=\"Markers\"'",
+            "Opened the stream EEG.",
+            "Opened the stream Markers.",
+            "Received header for stream EEG.",
+            "Started data collection for stream EEG.",
+        ] {
+            monitor.parse_line(line);
+        }
+
+        assert_eq!(
+            monitor.streams(),
+            [
+                stream("EEG", Some("lab-pc"), StreamState::Collecting),
+                stream("Markers", Some("stim-pc"), StreamState::Opened),
+            ]
+        );
+        assert!(monitor.wait_started(Duration::ZERO).is_ok());
+    }
+
+    #[test]
+    fn reports_unmatched_predicates_when_the_cli_exits() {
+        let monitor = Monitor::default();
+        monitor.parse_line("'type=\"Gaze\"' matched no stream!");
+        monitor.lock().closed = true;
+
+        let error = monitor.wait_started(Duration::from_secs(1)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "LabRecorderCLI exited before recording started; 'type=\"Gaze\"' matched no stream"
+        );
+    }
+}
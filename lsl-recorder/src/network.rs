@@ -0,0 +1,212 @@
+//! Generated `lsl_api.cfg` for networks without multicast.
+//!
+//! liblsl reads its configuration from the file named in the `LSLAPICFG`
+//! environment variable. The recorder writes the settings to a temporary file
+//! and points LabRecorderCLI at it, so no per-machine config file is needed.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use pyo3::{PyResult, exceptions::PyValueError, pyclass, pymethods};
+
+/// Environment variable liblsl reads the config file location from.
+pub const LSL_CONFIG_ENV_VAR: &str = "LSLAPICFG";
+
+/// Which IP protocols liblsl may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpVersion {
+    Ipv4,
+    Ipv6,
+    Both,
+}
+
+/// liblsl network settings, written as `lsl_api.cfg`.
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// Hosts to query directly instead of relying on multicast discovery.
+    pub known_peers: Vec<String>,
+    pub ip_version: Option<IpVersion>,
+    /// First port of the range liblsl binds to.
+    pub base_port: Option<u16>,
+    /// Number of ports liblsl may use, starting at `base_port`.
+    pub port_range: Option<u16>,
+    /// Only streams with the same session id are visible.
+    pub session_id: Option<String>,
+}
+
+impl NetworkConfig {
+    /// Contents of the `lsl_api.cfg` file.
+    pub fn to_cfg(&self) -> String {
+        let mut cfg = String::new();
+
+        if self.ip_version.is_some() || self.base_port.is_some() || self.port_range.is_some() {
+            cfg.push_str("[ports]\n");
+            if let Some(ip_version) = self.ip_version {
+                let ipv6 = match ip_version {
+                    IpVersion::Ipv4 => "disable",
+                    IpVersion::Ipv6 => "force",
+                    IpVersion::Both => "allow",
+                };
+                let _ = writeln!(cfg, "IPv6 = {}", ipv6);
+            }
+            if let Some(base_port) = self.base_port {
+                let _ = writeln!(cfg, "BasePort = {}", base_port);
+            }
+            if let Some(port_range) = self.port_range {
+                let _ = writeln!(cfg, "PortRange = {}", port_range);
+            }
+            cfg.push('\n');
+        }
+
+        if !self.known_peers.is_empty() || self.session_id.is_some() {
+            cfg.push_str("[lab]\n");
+            if !self.known_peers.is_empty() {
+                let _ = writeln!(cfg, "KnownPeers = {{{}}}", self.known_peers.join(", "));
+            }
+            if let Some(session_id) = &self.session_id {
+                let _ = writeln!(cfg, "SessionID = {}", session_id);
+            }
+        }
+
+        cfg
+    }
+
+    /// Write the settings to a new temporary file, removed again when dropped.
+    pub(crate) fn write_temp(&self) -> Result<TempConfig, std::io::Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join("lsl-recorder");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "lsl_api-{}-{}.cfg",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, self.to_cfg())?;
+        Ok(TempConfig { path })
+    }
+}
+
+#[pymethods]
+impl NetworkConfig {
+    /// liblsl network settings for LabRecorderCLI.
+    ///
+    /// `ip_version` is one of "ipv4", "ipv6" or "both".
+    #[new]
+    #[pyo3(signature = (known_peers = Vec::new(), ip_version = None, base_port = None, port_range = None, session_id = None))]
+    fn py_new(
+        known_peers: Vec<String>,
+        ip_version: Option<String>,
+        base_port: Option<u16>,
+        port_range: Option<u16>,
+        session_id: Option<String>,
+    ) -> PyResult<Self> {
+        let ip_version = match ip_version.as_deref() {
+            None => None,
+            Some("ipv4") => Some(IpVersion::Ipv4),
+            Some("ipv6") => Some(IpVersion::Ipv6),
+            Some("both") => Some(IpVersion::Both),
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid ip_version '{}', expected 'ipv4', 'ipv6' or 'both'",
+                    other
+                )));
+            }
+        };
+        Ok(NetworkConfig {
+            known_peers,
+            ip_version,
+            base_port,
+            port_range,
+            session_id,
+        })
+    }
+
+    /// Contents of the generated `lsl_api.cfg`.
+    #[pyo3(name = "to_cfg")]
+    fn py_to_cfg(&self) -> String {
+        self.to_cfg()
+    }
+}
+
+/// A generated config file that is deleted when dropped.
+#[derive(Debug)]
+pub(crate) struct TempConfig {
+    path: PathBuf,
+}
+
+impl TempConfig {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempConfig {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_nothing_for_the_default_config() {
+        assert_eq!(NetworkConfig::default().to_cfg(), "");
+    }
+
+    #[test]
+    fn writes_all_settings() {
+        let config = NetworkConfig {
+            known_peers: vec!["192.168.1.10".to_string(), "eeg-pc".to_string()],
+            ip_version: Some(IpVersion::Ipv4),
+            base_port: Some(16572),
+            port_range: Some(32),
+            session_id: Some("lab-a".to_string()),
+        };
+        assert_eq!(
+            config.to_cfg(),
+            "[ports]\n\
+             IPv6 = disable\n\
+             BasePort = 16572\n\
+             PortRange = 32\n\
+             \n\
+             [lab]\n\
+             KnownPeers = {192.168.1.10, eeg-pc}\n\
+             SessionID = lab-a\n"
+        );
+    }
+
+    #[test]
+    fn writes_only_the_sections_in_use() {
+        let ports = NetworkConfig {
+            ip_version: Some(IpVersion::Both),
+            ..NetworkConfig::default()
+        };
+        assert_eq!(ports.to_cfg(), "[ports]\nIPv6 = allow\n\n");
+
+        let lab = NetworkConfig {
+            known_peers: vec!["eeg-pc".to_string()],
+            ..NetworkConfig::default()
+        };
+        assert_eq!(lab.to_cfg(), "[lab]\nKnownPeers = {eeg-pc}\n");
+    }
+
+    #[test]
+    fn removes_the_temporary_file_when_dropped() {
+        let config = NetworkConfig {
+            session_id: Some("lab-a".to_string()),
+            ..NetworkConfig::default()
+        };
+        let temp = config.write_temp().unwrap();
+        let path = temp.path().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), config.to_cfg());
+        drop(temp);
+        assert!(!path.exists());
+    }
+}
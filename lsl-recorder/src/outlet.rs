@@ -0,0 +1,109 @@
+//! LSL outlets owned by the recorder: the annotation markers and the session info.
+//!
+//! liblsl comes from `liblsl-loader`; it is looked up in this order:
+//! 1. the `LSL_RECORDER_LIBLSL` environment variable,
+//! 2. the directory of LabRecorderCLI, which ships its own copy,
+//! 3. the system library path.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use liblsl_loader::{Api, CHANNEL_FORMAT_STRING, IRREGULAR_RATE, Outlet, StreamHeader};
+
+/// Environment variable that overrides the liblsl location.
+pub const LIBLSL_ENV_VAR: &str = "LSL_RECORDER_LIBLSL";
+
+/// Load liblsl, looking next to LabRecorderCLI in `cli_dir` if it is not found elsewhere.
+fn api(cli_dir: Option<&Path>) -> Result<&'static Api, std::io::Error> {
+    liblsl_loader::load(LIBLSL_ENV_VAR, cli_dir.as_slice())
+}
+
+/// Current time on the LSL clock, in seconds.
+pub(crate) fn local_clock(cli_dir: Option<&Path>) -> Result<f64, std::io::Error> {
+    Ok(api(cli_dir)?.local_clock())
+}
+
+/// An irregular single-channel string stream.
+pub(crate) struct StringOutlet {
+    outlet: Outlet,
+    source_id: String,
+}
+
+impl StringOutlet {
+    /// Create an outlet with a source id unique to this process.
+    ///
+    /// `desc` is written into the `<desc>` element of the stream header, one
+    /// child element per entry.
+    pub(crate) fn new(
+        name: &str,
+        kind: &str,
+        desc: &BTreeMap<String, String>,
+        cli_dir: Option<&Path>,
+    ) -> Result<Self, std::io::Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        if let Some(key) = desc.keys().find(|key| !is_xml_name(key)) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("'{}' is not a valid metadata key", key),
+            ));
+        }
+        let desc = desc
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let source_id = format!(
+            "lsl-recorder-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let outlet = Outlet::new(
+            api(cli_dir)?,
+            &StreamHeader {
+                name,
+                kind,
+                channel_count: 1,
+                nominal_rate: IRREGULAR_RATE,
+                channel_format: CHANNEL_FORMAT_STRING,
+                source_id: &source_id,
+                channel_labels: &[],
+                desc: &desc,
+            },
+        )?;
+
+        Ok(StringOutlet { outlet, source_id })
+    }
+
+    /// Search predicate that matches this outlet and nothing else.
+    pub(crate) fn predicate(&self) -> String {
+        format!("source_id=\"{}\"", self.source_id)
+    }
+
+    /// Wait up to `timeout` until a recorder has subscribed to the stream.
+    pub(crate) fn wait_for_consumers(&self, timeout: Duration) -> bool {
+        self.outlet.wait_for_consumers(timeout)
+    }
+
+    /// Push a sample, stamped with `timestamp` on the LSL clock or the current time.
+    ///
+    /// Returns the timestamp of the sample.
+    pub(crate) fn push(&self, sample: &str, timestamp: Option<f64>) -> Result<f64, std::io::Error> {
+        let timestamp = timestamp.unwrap_or_else(|| self.outlet.local_clock());
+        self.outlet.push_strings(&[sample], timestamp)?;
+        Ok(timestamp)
+    }
+}
+
+/// Whether `name` can be used as an XML element name in the stream header.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.to_ascii_lowercase().starts_with("xml")
+}
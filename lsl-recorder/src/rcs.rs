@@ -0,0 +1,422 @@
+//! Client for LabRecorder's remote control socket (RCS).
+//!
+//! The LabRecorder GUI listens on a TCP port (22345 by default, see `RCSPort`
+//! in `LabRecorder.cfg`) and accepts one text command per line. Every command
+//! is acknowledged with a line reading `OK`.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use pyo3::{Py, PyRef, PyResult, exceptions::PyRuntimeError, pyclass, pymethods};
+
+use crate::py_duration;
+
+/// Default port of the LabRecorder remote control socket.
+pub const DEFAULT_RCS_PORT: u16 = 22345;
+
+/// Placeholder fields of the LabRecorder `filename` command.
+///
+/// Fields left as `None` are not sent, so LabRecorder keeps its current value.
+/// Values must not contain `}` or line breaks, which would end the field or the
+/// command early.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RcsFilename {
+    pub root: Option<String>,
+    pub template: Option<String>,
+    pub participant: Option<String>,
+    pub session: Option<String>,
+    pub task: Option<String>,
+    pub acquisition: Option<String>,
+    pub modality: Option<String>,
+    pub run: Option<u32>,
+}
+
+/// A command understood by the LabRecorder remote control socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RcsCommand {
+    /// Refresh the list of available streams.
+    Update,
+    /// Select all streams for recording.
+    SelectAll,
+    /// Deselect all streams.
+    SelectNone,
+    /// Set the storage location and BIDS fields of the next recording.
+    Filename(RcsFilename),
+    /// Start recording.
+    Start,
+    /// Stop recording.
+    Stop,
+}
+
+impl fmt::Display for RcsCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RcsCommand::Update => write!(f, "update"),
+            RcsCommand::SelectAll => write!(f, "select all"),
+            RcsCommand::SelectNone => write!(f, "select none"),
+            RcsCommand::Start => write!(f, "start"),
+            RcsCommand::Stop => write!(f, "stop"),
+            RcsCommand::Filename(fields) => {
+                write!(f, "filename")?;
+                let text_fields = [
+                    ("root", &fields.root),
+                    ("template", &fields.template),
+                    ("participant", &fields.participant),
+                    ("session", &fields.session),
+                    ("task", &fields.task),
+                    ("acquisition", &fields.acquisition),
+                    ("modality", &fields.modality),
+                ];
+                for (key, value) in text_fields {
+                    if let Some(value) = value {
+                        // a value can't be escaped, refuse to cut it short
+                        if value.contains(['}', '\n', '\r']) {
+                            return Err(fmt::Error);
+                        }
+                        write!(f, " {{{}:{}}}", key, value)?;
+                    }
+                }
+                if let Some(run) = fields.run {
+                    write!(f, " {{run:{}}}", run)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Connection to a running LabRecorder instance.
+#[pyclass]
+pub struct RcsClient {
+    stream: TcpStream,
+}
+
+impl RcsClient {
+    /// Connect to the remote control socket at `addr`.
+    ///
+    /// `timeout` applies to connecting and to waiting for each acknowledgement.
+    pub fn connect(addr: impl ToSocketAddrs, timeout: Duration) -> Result<Self, std::io::Error> {
+        let mut last_error = std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Address did not resolve to any socket address",
+        );
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Ok(RcsClient { stream });
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Send a command and wait for LabRecorder to acknowledge it.
+    ///
+    /// Fails with `InvalidInput`, without sending anything, if a filename field
+    /// contains `}` or a line break.
+    pub fn send(&mut self, command: &RcsCommand) -> Result<(), std::io::Error> {
+        let mut line = String::new();
+        fmt::write(&mut line, format_args!("{}\n", command)).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Filename fields must not contain '}' or line breaks",
+            )
+        })?;
+        self.stream.write_all(line.as_bytes())?;
+        self.stream.flush()?;
+        self.wait_for_ack()
+    }
+
+    pub fn update(&mut self) -> Result<(), std::io::Error> {
+        self.send(&RcsCommand::Update)
+    }
+
+    pub fn select_all(&mut self) -> Result<(), std::io::Error> {
+        self.send(&RcsCommand::SelectAll)
+    }
+
+    pub fn select_none(&mut self) -> Result<(), std::io::Error> {
+        self.send(&RcsCommand::SelectNone)
+    }
+
+    pub fn set_filename(&mut self, fields: RcsFilename) -> Result<(), std::io::Error> {
+        self.send(&RcsCommand::Filename(fields))
+    }
+
+    pub fn start(&mut self) -> Result<(), std::io::Error> {
+        self.send(&RcsCommand::Start)
+    }
+
+    pub fn stop(&mut self) -> Result<(), std::io::Error> {
+        self.send(&RcsCommand::Stop)
+    }
+
+    fn wait_for_ack(&mut self) -> Result<(), std::io::Error> {
+        // LabRecorder answers with a line reading "OK", anything else is an error
+        // message; older versions omit the newline, so a reply also ends when the
+        // connection closes or goes quiet
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 64];
+        while !response.contains(&b'\n') {
+            match self.stream.read(&mut buffer) {
+                Ok(0) if response.is_empty() => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "LabRecorder closed the connection",
+                    ));
+                }
+                Ok(0) => break,
+                Ok(n) => response.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e)
+                    if !response.is_empty()
+                        && matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) =>
+                {
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let response = String::from_utf8_lossy(&response);
+        let reply = response.lines().next().unwrap_or_default().trim();
+        if reply == "OK" {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!(
+                "LabRecorder replied '{}'",
+                reply
+            )))
+        }
+    }
+}
+
+#[pymethods]
+impl RcsClient {
+    /// Connect to the remote control socket of a running LabRecorder.
+    #[new]
+    #[pyo3(signature = (host = "localhost".to_string(), port = DEFAULT_RCS_PORT, timeout = 5.0))]
+    fn py_new(host: String, port: u16, timeout: f64) -> PyResult<Self> {
+        let timeout = py_duration("timeout", timeout)?;
+        RcsClient::connect((host.as_str(), port), timeout).map_err(|e| {
+            PyRuntimeError::new_err(format!("Failed to connect to LabRecorder: {}", e))
+        })
+    }
+
+    /// Refresh the list of available streams.
+    #[pyo3(name = "update")]
+    fn py_update(&mut self) -> PyResult<()> {
+        self.update().map_err(command_error)
+    }
+
+    /// Select all streams for recording.
+    #[pyo3(name = "select_all")]
+    fn py_select_all(&mut self) -> PyResult<()> {
+        self.select_all().map_err(command_error)
+    }
+
+    /// Deselect all streams.
+    #[pyo3(name = "select_none")]
+    fn py_select_none(&mut self) -> PyResult<()> {
+        self.select_none().map_err(command_error)
+    }
+
+    /// Set the storage location and BIDS fields of the next recording.
+    #[pyo3(name = "set_filename")]
+    #[pyo3(signature = (root = None, template = None, participant = None, session = None, task = None, acquisition = None, modality = None, run = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_set_filename(
+        &mut self,
+        root: Option<String>,
+        template: Option<String>,
+        participant: Option<String>,
+        session: Option<String>,
+        task: Option<String>,
+        acquisition: Option<String>,
+        modality: Option<String>,
+        run: Option<u32>,
+    ) -> PyResult<()> {
+        self.set_filename(RcsFilename {
+            root,
+            template,
+            participant,
+            session,
+            task,
+            acquisition,
+            modality,
+            run,
+        })
+        .map_err(command_error)
+    }
+
+    /// Start recording.
+    #[pyo3(name = "start")]
+    fn py_start(&mut self) -> PyResult<()> {
+        self.start().map_err(command_error)
+    }
+
+    /// Stop recording.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) -> PyResult<()> {
+        self.stop().map_err(command_error)
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyResult<Py<Self>> {
+        Ok(slf.into())
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&mut self, _args: &pyo3::Bound<'_, pyo3::types::PyTuple>) -> PyResult<()> {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+        Ok(())
+    }
+}
+
+fn command_error(e: std::io::Error) -> pyo3::PyErr {
+    PyRuntimeError::new_err(format!("Failed to send command to LabRecorder: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use super::*;
+
+    /// Accept one connection, answer each command line with the next reply and
+    /// return the lines received.
+    fn fake_labrecorder(replies: &'static [&'static str]) -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut lines = Vec::new();
+            for (line, reply) in BufReader::new(stream).lines().zip(replies) {
+                lines.push(line.unwrap());
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+            lines
+        });
+        (port, server)
+    }
+
+    fn connect(port: u16) -> RcsClient {
+        RcsClient::connect(("127.0.0.1", port), Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn sends_one_command_per_line() {
+        let (port, server) = fake_labrecorder(&["OK\n", "OK\r\n", "OK\n", "OK\n"]);
+        let mut client = connect(port);
+        client.select_all().unwrap();
+        client
+            .set_filename(RcsFilename {
+                root: Some("/data/study".to_string()),
+                participant: Some("P001".to_string()),
+                task: Some("rest".to_string()),
+                run: Some(2),
+                ..RcsFilename::default()
+            })
+            .unwrap();
+        client.start().unwrap();
+        client.stop().unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "select all",
+                "filename {root:/data/study} {participant:P001} {task:rest} {run:2}",
+                "start",
+                "stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_replies_other_than_ok() {
+        let (port, server) = fake_labrecorder(&["OK\n", "Unknown command\n"]);
+        let mut client = connect(port);
+        client.update().unwrap();
+        let error = client.start().unwrap_err();
+        assert!(error.to_string().contains("Unknown command"), "{}", error);
+        drop(client);
+
+        assert_eq!(server.join().unwrap(), ["update", "start"]);
+    }
+
+    #[test]
+    fn accepts_only_ok() {
+        let (port, server) = fake_labrecorder(&["NOT OK\n", "OK, but\n", "Error: OK\n"]);
+        let mut client = connect(port);
+        for _ in 0..3 {
+            assert!(client.update().is_err());
+        }
+        drop(client);
+        assert_eq!(server.join().unwrap(), ["update"; 3]);
+    }
+
+    #[test]
+    fn accepts_ok_without_newline_when_the_reply_ends() {
+        // at a timeout, the connection stays open
+        let (port, server) = fake_labrecorder(&["OK"]);
+        let mut client =
+            RcsClient::connect(("127.0.0.1", port), Duration::from_millis(200)).unwrap();
+        client.start().unwrap();
+        drop(client);
+        assert_eq!(server.join().unwrap(), ["start"]);
+
+        // at EOF, LabRecorder closed it after replying
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            stream.write_all(b"OK").unwrap();
+            line
+        });
+        connect(port).stop().unwrap();
+        assert_eq!(server.join().unwrap(), "stop\n");
+    }
+
+    #[test]
+    fn refuses_fields_that_would_break_the_command() {
+        let (port, server) = fake_labrecorder(&["OK\n"]);
+        let mut client = connect(port);
+        for participant in ["P001} {run:9", "P001\nstart"] {
+            let error = client
+                .set_filename(RcsFilename {
+                    participant: Some(participant.to_string()),
+                    ..RcsFilename::default()
+                })
+                .unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        client.update().unwrap();
+        drop(client);
+        assert_eq!(server.join().unwrap(), ["update"]);
+    }
+
+    #[test]
+    fn fails_when_labrecorder_disconnects() {
+        let (port, server) = fake_labrecorder(&[]);
+        let mut client = connect(port);
+        assert!(client.select_none().is_err());
+        assert!(server.join().unwrap().is_empty());
+    }
+}
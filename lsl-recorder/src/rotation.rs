@@ -0,0 +1,151 @@
+//! Splitting long recordings into numbered runs.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How often the rotation thread checks whether it has been stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// File name of run number `run` of a recording that started as `first`.
+///
+/// An existing BIDS `_run-<index>` entity is replaced, keeping its zero padding,
+/// e.g. `sub-01_run-01_eeg.xdf` becomes `sub-01_run-02_eeg.xdf`. Otherwise
+/// `_run-<index>` is appended to the file stem, e.g. `recording_run-02.xdf`.
+pub fn next_run_filename(first: &Path, run: usize) -> PathBuf {
+    let stem = first
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = first
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let stem = match stem.find("_run-") {
+        Some(start) => {
+            let digits_start = start + "_run-".len();
+            let digits = stem[digits_start..]
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            format!(
+                "{}{:0width$}{}",
+                &stem[..digits_start],
+                run,
+                &stem[digits_start + digits..],
+                width = digits.max(1)
+            )
+        }
+        None => format!("{}_run-{:02}", stem, run),
+    };
+
+    first.with_file_name(format!("{}{}", stem, extension))
+}
+
+/// Call `split` every `interval` on a background thread until `done` is set.
+///
+/// `split` returns the gap between the files, or `None` once the recording has
+/// been stopped or dropped, which ends the thread without an error.
+pub(crate) fn spawn_rotation(
+    interval: Duration,
+    done: Arc<AtomicBool>,
+    split: impl Fn() -> Result<Option<Duration>, std::io::Error> + Send + 'static,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut next_split = Instant::now() + interval;
+        while !done.load(Ordering::Relaxed) {
+            if Instant::now() >= next_split {
+                match split() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => return,
+                    Err(e) => {
+                        eprintln!("Failed to split recording: {}", e);
+                        return;
+                    }
+                }
+                next_split += interval;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(first: &str, run: usize) -> PathBuf {
+        next_run_filename(Path::new(first), run)
+    }
+
+    #[test]
+    fn increments_existing_run_entity() {
+        assert_eq!(
+            next("data/sub-01_ses-01_task-rest_run-01_eeg.xdf", 2),
+            Path::new("data/sub-01_ses-01_task-rest_run-02_eeg.xdf")
+        );
+        assert_eq!(
+            next("sub-01_run-001.xdf", 2),
+            Path::new("sub-01_run-002.xdf")
+        );
+    }
+
+    #[test]
+    fn appends_run_without_run_entity() {
+        assert_eq!(next("recording.xdf", 2), Path::new("recording_run-02.xdf"));
+        assert_eq!(
+            next("/tmp/recording", 3),
+            Path::new("/tmp/recording_run-03")
+        );
+    }
+
+    #[test]
+    fn grows_padding_for_multi_digit_runs() {
+        assert_eq!(
+            next("sub-01_run-9_eeg.xdf", 10),
+            Path::new("sub-01_run-10_eeg.xdf")
+        );
+        assert_eq!(
+            next("sub-01_run-01_eeg.xdf", 123),
+            Path::new("sub-01_run-123_eeg.xdf")
+        );
+        assert_eq!(
+            next("recording.xdf", 100),
+            Path::new("recording_run-100.xdf")
+        );
+    }
+
+    #[test]
+    fn stops_quietly_once_the_recording_has_stopped() {
+        let splits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let rotation = {
+            let splits = splits.clone();
+            spawn_rotation(
+                Duration::from_millis(10),
+                Arc::new(AtomicBool::new(false)),
+                move || match splits.fetch_add(1, Ordering::Relaxed) {
+                    0 => Ok(Some(Duration::ZERO)),
+                    _ => Ok(None),
+                },
+            )
+        };
+        rotation.join().unwrap();
+        assert_eq!(splits.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn stops_when_done() {
+        let done = Arc::new(AtomicBool::new(true));
+        let rotation = spawn_rotation(Duration::ZERO, done, || {
+            panic!("split after the recording was done")
+        });
+        rotation.join().unwrap();
+    }
+}
@@ -0,0 +1,569 @@
+//! Unattended recordings that start at a given time, stop after a duration and
+//! optionally repeat.
+//!
+//! A background thread waits for each start time, runs an `LSLStreamRecorder`
+//! for one run and stops it again. Every run is written to its own numbered
+//! file, see `next_run_filename`.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use pyo3::{
+    Bound, Py, PyAny, PyRef, PyResult, Python,
+    exceptions::{PyRuntimeError, PyValueError},
+    pyclass, pymethods,
+};
+
+use crate::{
+    DEFAULT_STOP_TIMEOUT, LSLStreamRecorder, NetworkConfig, RecorderOptions, RecorderState,
+    RecorderStatus, StopReason, discovery, outlet, py_duration, py_find_cli,
+    rotation::next_run_filename,
+};
+
+/// How often the schedule thread checks on a running recording.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// When the first run of a scheduled recording starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StartAt {
+    #[default]
+    Now,
+    /// A wall-clock time.
+    Time(SystemTime),
+    /// A timestamp on the LSL clock, in seconds.
+    Lsl(f64),
+}
+
+/// Timing of a scheduled recording.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    pub start_at: StartAt,
+    /// Stop each run after this long. Without it, a run lasts until the next one
+    /// starts or the schedule is cancelled.
+    pub stop_after: Option<Duration>,
+    /// Start another run at this interval, counted from the previous start.
+    pub repeat_every: Option<Duration>,
+    /// Number of runs when repeating; unlimited if not set.
+    pub runs: Option<usize>,
+}
+
+#[derive(Default)]
+struct Progress {
+    /// The recorder of the current (or last) run.
+    current: Option<LSLStreamRecorder>,
+    files: Vec<PathBuf>,
+    next_start: Option<Instant>,
+    cancelled: bool,
+}
+
+#[derive(Default)]
+struct ScheduleShared {
+    progress: Mutex<Progress>,
+    changed: Condvar,
+}
+
+impl ScheduleShared {
+    fn lock(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A recording that is started and stopped by a background thread.
+#[pyclass]
+pub struct ScheduledRecorder {
+    shared: Arc<ScheduleShared>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    filename: PathBuf,
+}
+
+impl ScheduledRecorder {
+    /// Schedule a recording of all streams matching any of `predicates`.
+    ///
+    /// `timeout` is how long each run waits for its streams. A run that fails to
+    /// start is skipped; the schedule continues with the next one.
+    pub fn start(
+        filename: &str,
+        predicates: &[String],
+        timeout: Duration,
+        cli_path: Option<&str>,
+        options: RecorderOptions,
+        schedule: Schedule,
+    ) -> Result<Self, std::io::Error> {
+        if options.split_every.is_some() && schedule.repeat_every.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "split_every and repeat_every cannot be combined",
+            ));
+        }
+        // surface a missing LabRecorderCLI now rather than at the first start
+        let cli_path = discovery::find_cli(cli_path.map(Path::new), None)?;
+
+        let now = Instant::now();
+        let first_start = match schedule.start_at {
+            StartAt::Now => now,
+            StartAt::Time(time) => {
+                now + time
+                    .duration_since(SystemTime::now())
+                    .unwrap_or(Duration::ZERO)
+            }
+            StartAt::Lsl(timestamp) => {
+                let lsl_now = outlet::local_clock(cli_path.parent())?;
+                now + Duration::try_from_secs_f64(timestamp - lsl_now).unwrap_or(Duration::ZERO)
+            }
+        };
+
+        let shared = Arc::new(ScheduleShared::default());
+        // report the schedule before the thread has picked it up
+        shared.lock().next_start = Some(first_start);
+        let first = PathBuf::from(filename);
+        let predicates = predicates.to_vec();
+        let start_run = move |file: &Path| {
+            LSLStreamRecorder::with_predicates(
+                &file.to_string_lossy(),
+                &predicates,
+                timeout,
+                cli_path.to_str(),
+                options.clone(),
+            )
+        };
+        let thread = {
+            let shared = shared.clone();
+            let first = first.clone();
+            std::thread::spawn(move || {
+                run_schedule(&shared, &schedule, first_start, &first, start_run)
+            })
+        };
+
+        Ok(ScheduledRecorder {
+            shared,
+            thread: Mutex::new(Some(thread)),
+            filename: first,
+        })
+    }
+
+    /// Stop the current run, if any, and cancel all runs that have not started yet.
+    ///
+    /// A run that is still waiting for its streams is stopped as soon as it has
+    /// started. Returns once the schedule thread has finished.
+    pub fn cancel(&self) {
+        self.shared.lock().cancelled = true;
+        self.shared.changed.notify_all();
+        let thread = self
+            .thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+
+    /// Status of the current run, or `Scheduled` with the time of the next start.
+    pub fn status(&self) -> RecorderStatus {
+        let progress = self.shared.lock();
+        let next_start = progress
+            .next_start
+            .map(|start| SystemTime::now() + start.saturating_duration_since(Instant::now()));
+        match &progress.current {
+            Some(recorder) => {
+                let mut status = recorder.status();
+                if status.state == RecorderState::Exited && next_start.is_some() {
+                    status.state = RecorderState::Scheduled;
+                }
+                status.next_start = next_start;
+                status
+            }
+            None => RecorderStatus {
+                state: if next_start.is_some() {
+                    RecorderState::Scheduled
+                } else {
+                    RecorderState::Exited
+                },
+                elapsed: Duration::ZERO,
+                streams: Vec::new(),
+                bytes_written: 0,
+                exit_code: None,
+                stop_reason: None,
+                filename: self.filename.clone(),
+                next_start,
+            },
+        }
+    }
+
+    /// The recorder of the current or last run, or `None` before the first start.
+    pub fn recorder(&self) -> Option<LSLStreamRecorder> {
+        self.shared.lock().current.clone()
+    }
+
+    /// Files of all runs that have started so far.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.shared.lock().files.clone()
+    }
+}
+
+impl Drop for ScheduledRecorder {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn run_schedule(
+    shared: &ScheduleShared,
+    schedule: &Schedule,
+    first_start: Instant,
+    first: &Path,
+    start_run: impl Fn(&Path) -> Result<LSLStreamRecorder, std::io::Error>,
+) {
+    let mut start = first_start;
+    let mut run = 1;
+    loop {
+        // wait for the start time
+        {
+            let mut progress = shared.lock();
+            progress.next_start = Some(start);
+            while !progress.cancelled && Instant::now() < start {
+                let wait = start - Instant::now();
+                progress = shared
+                    .changed
+                    .wait_timeout(progress, wait)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+            progress.next_start = None;
+            if progress.cancelled {
+                return;
+            }
+        }
+
+        let file = if run == 1 {
+            first.to_path_buf()
+        } else {
+            next_run_filename(first, run)
+        };
+        let recorder = match start_run(&file) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!(
+                    "Failed to start scheduled recording {}: {}",
+                    file.display(),
+                    e
+                );
+                None
+            }
+        };
+        let started = Instant::now();
+
+        let mut following = schedule
+            .repeat_every
+            .filter(|_| schedule.runs.is_none_or(|runs| run < runs))
+            .map(|interval| {
+                // skip starts that were missed while this run was starting
+                let mut following = start + interval;
+                while following < started {
+                    following += interval;
+                }
+                following
+            });
+        let stop_at = schedule.stop_after.map(|stop_after| started + stop_after);
+
+        // record until the stop time, the next start or a cancel
+        let mut progress = shared.lock();
+        if let Some(recorder) = &recorder {
+            progress.files.push(file);
+            progress.current = Some(recorder.clone());
+        }
+        progress.next_start = following;
+        let reason = loop {
+            if progress.cancelled {
+                following = None;
+                break StopReason::Requested;
+            }
+            let now = Instant::now();
+            if stop_at.is_some_and(|stop_at| now >= stop_at)
+                || following.is_some_and(|following| now >= following)
+            {
+                break StopReason::Scheduled;
+            }
+            // nothing left to do once the only run has ended or failed to start
+            if following.is_none()
+                && recorder
+                    .as_ref()
+                    .is_none_or(|recorder| recorder.status().state == RecorderState::Exited)
+            {
+                return;
+            }
+            progress = shared
+                .changed
+                .wait_timeout(progress, POLL_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        };
+        drop(progress);
+
+        if let Some(recorder) = &recorder
+            && let Err(e) = recorder.stop_with_reason(reason, DEFAULT_STOP_TIMEOUT)
+        {
+            eprintln!("Failed to stop scheduled recording: {}", e);
+        }
+
+        match following {
+            Some(following) => {
+                start = following;
+                run += 1;
+            }
+            None => {
+                shared.lock().next_start = None;
+                return;
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl ScheduledRecorder {
+    /// Schedule a recording that is started and stopped by a background thread.
+    ///
+    /// The first run starts at `start_at` (unix time) or `start_at_lsl` (LSL clock),
+    /// or right away. Each run stops after `stop_after` seconds; with
+    /// `repeat_every` (seconds), a new run into the next numbered file starts at
+    /// that interval, up to `runs` times. The other arguments are those of
+    /// `LSLStreamRecorder`.
+    #[new]
+    #[pyo3(signature = (filename, seearchstring, timeout, start_at = None, start_at_lsl = None, stop_after = None, repeat_every = None, runs = None, max_file_size = None, min_free_disk = None, cli_path = None, network = None, marker_stream = None, metadata = None, journal = None, checksums = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        filename: String,
+        seearchstring: String,
+        timeout: f64,
+        start_at: Option<f64>,
+        start_at_lsl: Option<f64>,
+        stop_after: Option<f64>,
+        repeat_every: Option<f64>,
+        runs: Option<usize>,
+        max_file_size: Option<u64>,
+        min_free_disk: Option<u64>,
+        cli_path: Option<String>,
+        network: Option<NetworkConfig>,
+        marker_stream: Option<String>,
+        metadata: Option<BTreeMap<String, String>>,
+        journal: Option<PathBuf>,
+        checksums: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let start_at = match (start_at, start_at_lsl) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "Pass either start_at or start_at_lsl, not both",
+                ));
+            }
+            (Some(time), None) => {
+                StartAt::Time(SystemTime::UNIX_EPOCH + py_duration("start_at", time)?)
+            }
+            (None, Some(timestamp)) => StartAt::Lsl(timestamp),
+            (None, None) => StartAt::Now,
+        };
+        let schedule = Schedule {
+            start_at,
+            stop_after: stop_after
+                .map(|seconds| py_duration("stop_after", seconds))
+                .transpose()?,
+            repeat_every: repeat_every
+                .map(|seconds| py_duration("repeat_every", seconds))
+                .transpose()?,
+            runs,
+        };
+        let mut options = RecorderOptions {
+            network,
+            marker_stream,
+            metadata: metadata.unwrap_or_default(),
+            journal,
+            checksums,
+            ..RecorderOptions::default()
+        };
+        options.guards.max_file_size = max_file_size;
+        options.guards.min_free_disk = min_free_disk;
+        let timeout = py_duration("timeout", timeout)?;
+        let cli_path = py_find_cli(cli_path, py)?;
+
+        py.allow_threads(|| {
+            ScheduledRecorder::start(
+                &filename,
+                &[seearchstring],
+                timeout,
+                cli_path.to_str(),
+                options,
+                schedule,
+            )
+        })
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to schedule recording: {}", e)))
+    }
+
+    /// Stop the current run and cancel the ones that have not started yet.
+    #[pyo3(name = "cancel")]
+    fn py_cancel(&self, py: Python) {
+        py.allow_threads(|| self.cancel());
+    }
+
+    /// Status of the current run, or state `Scheduled` with `next_start` while waiting.
+    #[pyo3(name = "status")]
+    fn py_status(&self) -> RecorderStatus {
+        self.status()
+    }
+
+    /// The LSLStreamRecorder of the current or last run, or None before the first start.
+    #[getter(recorder)]
+    fn py_recorder(&self) -> Option<LSLStreamRecorder> {
+        self.recorder()
+    }
+
+    /// Files of all runs that have started so far.
+    #[getter(files)]
+    fn py_files(&self) -> Vec<String> {
+        self.files()
+            .iter()
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyResult<Py<Self>> {
+        Ok(slf.into())
+    }
+
+    fn __exit__(
+        slf: PyRef<Self>,
+        _exc_type: Bound<'_, PyAny>,
+        _exc_value: Bound<'_, PyAny>,
+        _traceback: Bound<'_, PyAny>,
+    ) {
+        let py = slf.py();
+        slf.py_cancel(py);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start times and files of the attempted runs.
+    type Attempts = Arc<Mutex<Vec<(Instant, PathBuf)>>>;
+
+    /// Run `schedule` on a thread with runs that fail to start, recording when
+    /// each was attempted and with which file.
+    fn run(
+        schedule: Schedule,
+        first_start: Instant,
+    ) -> (Arc<ScheduleShared>, Attempts, JoinHandle<()>) {
+        let shared = Arc::new(ScheduleShared::default());
+        let attempts = Attempts::default();
+        // as ScheduledRecorder::start does
+        shared.lock().next_start = Some(first_start);
+        let thread = {
+            let shared = shared.clone();
+            let attempts = attempts.clone();
+            std::thread::spawn(move || {
+                run_schedule(
+                    &shared,
+                    &schedule,
+                    first_start,
+                    Path::new("sub-P001.xdf"),
+                    |file| {
+                        attempts
+                            .lock()
+                            .unwrap()
+                            .push((Instant::now(), file.to_path_buf()));
+                        Err(std::io::Error::other("no streams"))
+                    },
+                )
+            })
+        };
+        (shared, attempts, thread)
+    }
+
+    fn cancel(shared: &ScheduleShared) {
+        shared.lock().cancelled = true;
+        shared.changed.notify_all();
+    }
+
+    #[test]
+    fn waits_for_the_start_time() {
+        let first_start = Instant::now() + Duration::from_millis(300);
+        let (shared, attempts, thread) = run(Schedule::default(), first_start);
+        assert!(shared.lock().next_start.is_some());
+        thread.join().unwrap();
+
+        let attempts = attempts.lock().unwrap();
+        assert_eq!(attempts.len(), 1);
+        assert!(attempts[0].0 >= first_start);
+        assert_eq!(attempts[0].1, Path::new("sub-P001.xdf"));
+        assert_eq!(shared.lock().next_start, None);
+    }
+
+    #[test]
+    fn repeats_into_numbered_files() {
+        let schedule = Schedule {
+            repeat_every: Some(Duration::from_millis(100)),
+            runs: Some(3),
+            ..Schedule::default()
+        };
+        let first_start = Instant::now();
+        let (_, attempts, thread) = run(schedule, first_start);
+        thread.join().unwrap();
+
+        let attempts = attempts.lock().unwrap();
+        let files = attempts
+            .iter()
+            .map(|(_, file)| file.clone())
+            .collect::<Vec<_>>();
+        let first = Path::new("sub-P001.xdf");
+        assert_eq!(
+            files,
+            [
+                first.to_path_buf(),
+                next_run_filename(first, 2),
+                next_run_filename(first, 3)
+            ]
+        );
+        for (run, (started, _)) in attempts.iter().enumerate() {
+            assert!(*started >= first_start + Duration::from_millis(100) * run as u32);
+        }
+    }
+
+    #[test]
+    fn cancels_before_the_first_start() {
+        let (shared, attempts, thread) = run(
+            Schedule::default(),
+            Instant::now() + Duration::from_secs(60),
+        );
+        std::thread::sleep(Duration::from_millis(50));
+        let cancelled = Instant::now();
+        cancel(&shared);
+        thread.join().unwrap();
+
+        assert!(cancelled.elapsed() < Duration::from_secs(5));
+        assert!(attempts.lock().unwrap().is_empty());
+        assert_eq!(shared.lock().next_start, None);
+    }
+
+    #[test]
+    fn cancels_the_remaining_runs() {
+        let schedule = Schedule {
+            repeat_every: Some(Duration::from_millis(100)),
+            ..Schedule::default()
+        };
+        let (shared, attempts, thread) = run(schedule, Instant::now());
+        while attempts.lock().unwrap().len() < 2 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        cancel(&shared);
+        thread.join().unwrap();
+
+        let runs = attempts.lock().unwrap().len();
+        assert!((2..=3).contains(&runs), "{}", runs);
+        assert_eq!(shared.lock().next_start, None);
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use pyo3::{pyclass, pymethods};
+
+use crate::events::StopReason;
+
+/// Lifecycle of an `LSLStreamRecorder`.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecorderState {
+    /// A scheduled recording is waiting for its start time.
+    Scheduled,
+    /// LabRecorderCLI is starting and has not collected data yet.
+    Starting,
+    /// Data is being written to the file.
+    Recording,
+    /// The file is being finalized.
+    Stopping,
+    /// LabRecorderCLI is no longer running.
+    Exited,
+}
+
+/// Progress of a single stream, as reported by LabRecorderCLI.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    /// The stream matched the search string.
+    Found,
+    /// An inlet was opened for the stream.
+    Opened,
+    /// The stream header was written to the file.
+    HeaderReceived,
+    /// Samples of the stream are being recorded.
+    Collecting,
+}
+
+impl fmt::Display for StreamState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StreamState::Found => "found",
+            StreamState::Opened => "opened",
+            StreamState::HeaderReceived => "header_received",
+            StreamState::Collecting => "collecting",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[pyclass(get_all)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamStatus {
+    pub name: String,
+    /// Host the stream was found on.
+    pub hostname: Option<String>,
+    pub state: StreamState,
+}
+
+/// Snapshot of a running (or finished) recording.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct RecorderStatus {
+    #[pyo3(get)]
+    pub state: RecorderState,
+    /// Time since the recording was started.
+    pub elapsed: Duration,
+    #[pyo3(get)]
+    pub streams: Vec<StreamStatus>,
+    /// Size of all files written so far, in bytes.
+    #[pyo3(get)]
+    pub bytes_written: u64,
+    /// Exit code of LabRecorderCLI once it has exited.
+    #[pyo3(get)]
+    pub exit_code: Option<i32>,
+    pub stop_reason: Option<StopReason>,
+    /// The file currently being recorded to.
+    pub filename: PathBuf,
+    /// When a scheduled recording starts its next run.
+    pub next_start: Option<SystemTime>,
+}
+
+#[pymethods]
+impl RecorderStatus {
+    /// Seconds since the recording was started.
+    #[getter(elapsed)]
+    fn py_elapsed(&self) -> f64 {
+        self.elapsed.as_secs_f64()
+    }
+
+    #[getter(stop_reason)]
+    fn py_stop_reason(&self) -> Option<String> {
+        self.stop_reason.map(|reason| reason.to_string())
+    }
+
+    #[getter(filename)]
+    fn py_filename(&self) -> String {
+        self.filename.to_string_lossy().into_owned()
+    }
+
+    /// Unix time of the next scheduled start, or None.
+    #[getter(next_start)]
+    fn py_next_start(&self) -> Option<f64> {
+        self.next_start.map(|time| {
+            time.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or_default()
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RecorderStatus(state={:?}, elapsed={:.1}, streams={}, bytes_written={}, exit_code={})",
+            self.state,
+            self.elapsed.as_secs_f64(),
+            self.streams.len(),
+            self.bytes_written,
+            self.exit_code
+                .map_or_else(|| "None".to_string(), |code| code.to_string())
+        )
+    }
+}
@@ -0,0 +1,162 @@
+//! Checksum sidecars of finished recordings, shared by lsl-recorder and webcam-py.
+//!
+//! A finished file gets two sidecars: `<file>.sha256` in the format of
+//! `sha256sum`, and `<file>.json` with the file name, the size, the checksum,
+//! the creation time and a summary of the recorded streams. Both recorders write
+//! them through this crate, so `lsl_recorder.verify_integrity()` can check
+//! either kind of file.
+
+use std::{
+    ffi::OsString,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Location of the `sha256sum`-style checksum of `path`.
+pub fn checksum_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".sha256")
+}
+
+/// Location of the JSON summary of `path`.
+pub fn summary_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".json")
+}
+
+/// Hex-encoded SHA-256 of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Write the `.sha256` and `.json` sidecars of `path`, whose checksum is `sha256`.
+///
+/// `streams` is the per-stream summary stored in the `.json` sidecar.
+pub fn write(path: &Path, sha256: &str, streams: Vec<Value>) -> Result<(), std::io::Error> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let summary = json!({
+        "filename": name,
+        "size": std::fs::metadata(path)?.len(),
+        "sha256": sha256,
+        "created_at": SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default(),
+        "streams": streams,
+    });
+
+    std::fs::write(checksum_path(path), format!("{}  {}\n", sha256, name))?;
+    std::fs::write(
+        summary_path(path),
+        serde_json::to_string_pretty(&summary).map_err(std::io::Error::other)?,
+    )
+}
+
+/// What the sidecars of a file say it should look like.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expected {
+    /// Lower-case hex SHA-256 from the `.sha256` sidecar.
+    pub sha256: String,
+    /// Size from the `.json` sidecar, if there is one.
+    pub size: Option<u64>,
+}
+
+/// Read the sidecars of `path`.
+///
+/// Fails if the `.sha256` sidecar is missing or does not hold a SHA-256; the
+/// `.json` sidecar is optional.
+pub fn read(path: &Path) -> Result<Expected, std::io::Error> {
+    let checksum = std::fs::read_to_string(checksum_path(path))?;
+    let sha256 = checksum
+        .split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "{} is not a SHA-256 checksum",
+                    checksum_path(path).display()
+                ),
+            )
+        })?
+        .to_ascii_lowercase();
+    let size = std::fs::read_to_string(summary_path(path))
+        .ok()
+        .and_then(|summary| serde_json::from_str::<Value>(&summary).ok())
+        .and_then(|summary| summary["size"].as_u64());
+    Ok(Expected { sha256, size })
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let dir = std::env::temp_dir().join(format!("recording-sidecars-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sub-P001_run-1.xdf");
+        std::fs::write(&path, b"recording").unwrap();
+
+        let sha256 = sha256_file(&path).unwrap();
+        write(&path, &sha256, vec![json!({"name": "EEG"})]).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(checksum_path(&path)).unwrap(),
+            format!("{}  sub-P001_run-1.xdf\n", sha256)
+        );
+        let summary: Value =
+            serde_json::from_str(&std::fs::read_to_string(summary_path(&path)).unwrap()).unwrap();
+        assert_eq!(summary["filename"], "sub-P001_run-1.xdf");
+        assert_eq!(summary["streams"][0]["name"], "EEG");
+        assert_eq!(
+            read(&path).unwrap(),
+            Expected {
+                sha256,
+                size: Some(9)
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_malformed_checksum() {
+        let dir =
+            std::env::temp_dir().join(format!("recording-sidecars-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("video.mkv");
+        std::fs::write(checksum_path(&path), "not a checksum\n").unwrap();
+
+        let error = read(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -10,8 +10,25 @@ fn main() {
     };
     // 8500 kbps
     let bitrate = None; // 8500 kbps
-    let recorder = Recorder::new(caps, "output.mkv".to_string(), false, 0, bitrate)
-        .expect("Failed to create recorder");
+    let recorder = Recorder::new(
+        caps,
+        "output.mkv".to_string(),
+        false,
+        None,
+        0,
+        bitrate,
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .expect("Failed to create recorder");
     println!("Starting recorder...");
     // // keep printing the last frame time
     // while recorder.is_recording() {
@@ -0,0 +1,122 @@
+//! Output container selection.
+
+use std::{path::Path, time::Duration};
+
+use crate::Codec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mkv,
+    WebM,
+    Mp4,
+    Mov,
+    /// MP4 written as a series of fragments; everything up to the last
+    /// complete fragment stays playable if the recording never sees EOS.
+    FragmentedMp4,
+}
+
+// length of an MP4 fragment, and so the most that is lost on a crash
+const FRAGMENT_DURATION_MS: u32 = 1000;
+
+impl Container {
+    /// Container implied by the file extension, if it is a known one.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mkv" => Some(Container::Mkv),
+            "webm" => Some(Container::WebM),
+            "mp4" | "m4v" => Some(Container::Mp4),
+            "mov" => Some(Container::Mov),
+            _ => None,
+        }
+    }
+
+    pub fn supports(&self, codec: Codec) -> bool {
+        match self {
+            Container::Mkv => true,
+            Container::WebM => matches!(codec, Codec::Vp8 | Codec::Vp9 | Codec::Av1),
+            Container::Mp4 | Container::FragmentedMp4 => matches!(
+                codec,
+                Codec::H264 | Codec::H265 | Codec::Vp9 | Codec::Av1 | Codec::Mjpeg
+            ),
+            Container::Mov => matches!(codec, Codec::H264 | Codec::H265 | Codec::Mjpeg),
+        }
+    }
+
+    pub(crate) fn muxer(&self) -> Result<gst::Element, gst::glib::Error> {
+        let (factory, plugins) = match self {
+            Container::Mkv => ("matroskamux", "gst-plugins-good"),
+            Container::WebM => ("webmmux", "gst-plugins-good"),
+            Container::Mp4 | Container::FragmentedMp4 => ("mp4mux", "gst-plugins-good"),
+            Container::Mov => ("qtmux", "gst-plugins-good"),
+        };
+        let muxer = gst::ElementFactory::make(factory).build().map_err(|_| {
+            gst::glib::Error::new(
+                gst::CoreError::MissingPlugin,
+                &format!("{} is not installed ({})", factory, plugins),
+            )
+        })?;
+        if *self == Container::FragmentedMp4 {
+            muxer.set_property("fragment-duration", FRAGMENT_DURATION_MS);
+        }
+        Ok(muxer)
+    }
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Container::Mkv => "mkv",
+            Container::WebM => "webm",
+            Container::Mp4 => "mp4",
+            Container::Mov => "mov",
+            Container::FragmentedMp4 => "fmp4",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Container {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mkv" | "matroska" => Ok(Container::Mkv),
+            "webm" => Ok(Container::WebM),
+            "mp4" => Ok(Container::Mp4),
+            "mov" => Ok(Container::Mov),
+            "fmp4" | "fragmented_mp4" => Ok(Container::FragmentedMp4),
+            _ => Err(format!("Unknown container '{}'", s)),
+        }
+    }
+}
+
+/// Splitting of a recording into segment files, each starting on a keyframe.
+///
+/// A new segment starts once either limit is reached; the filename is then a
+/// `printf` pattern such as `video_%03d.mkv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Segmenting {
+    pub max_duration: Option<Duration>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Segmenting {
+    pub(crate) fn sink(&self, pattern: &str, muxer: &gst::Element) -> gst::Element {
+        let sink = gst::ElementFactory::make("splitmuxsink")
+            .property("location", pattern)
+            .property("muxer", muxer)
+            .build()
+            .unwrap();
+        if let Some(duration) = self.max_duration {
+            sink.set_property("max-size-time", duration.as_nanos() as u64);
+        }
+        match self.max_bytes {
+            Some(bytes) => sink.set_property("max-size-bytes", bytes),
+            // ask the encoder for a keyframe when a segment is due, so segments
+            // don't overshoot by up to a whole keyframe interval
+            None => sink.set_property("send-keyframe-requests", true),
+        }
+        sink
+    }
+}
@@ -0,0 +1,587 @@
+//! Enumeration of attached cameras and the formats they support.
+
+use pyo3::prelude::*;
+
+use crate::CameraCaps;
+
+/// A video capture device as reported by GStreamer.
+#[derive(Debug, Clone)]
+#[pyclass(name = "CameraInfo")]
+pub struct CameraInfo {
+    /// Human readable name, e.g. "HD Pro Webcam C920".
+    #[pyo3(get)]
+    pub name: String,
+    /// Platform identifier of the device (`/dev/video0` on Linux, the device
+    /// path or unique id on Windows and macOS).
+    #[pyo3(get)]
+    pub device: String,
+    /// Position among the video sources reported by the device monitor.
+    #[pyo3(get)]
+    pub index: usize,
+    /// Every width/height/framerate/format combination the device offers.
+    #[pyo3(get)]
+    pub caps: Vec<CameraCaps>,
+    /// The device as found by the monitor, which creates its source element.
+    pub gst_device: Option<gst::Device>,
+}
+
+#[pymethods]
+impl CameraInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "CameraInfo(index={}, name='{}', device='{}', caps=[{} formats])",
+            self.index,
+            self.name,
+            self.device,
+            self.caps.len()
+        )
+    }
+}
+
+/// Source of camera descriptions. [`GstDeviceProvider`] queries the system;
+/// tests can pass their own implementation to [`list_cameras_with`].
+pub trait DeviceProvider {
+    fn devices(&self) -> Result<Vec<CameraInfo>, gst::glib::Error>;
+}
+
+/// Lists video sources through a `gst::DeviceMonitor`.
+pub struct GstDeviceProvider;
+
+// properties that carry the device identifier, by platform plugin
+const DEVICE_PROPERTIES: [&str; 5] = [
+    "device.path",
+    "api.v4l2.path",
+    "object.path",
+    "device.strid",
+    "avf.unique_id",
+];
+
+impl DeviceProvider for GstDeviceProvider {
+    fn devices(&self) -> Result<Vec<CameraInfo>, gst::glib::Error> {
+        gst::init()?;
+
+        let monitor = gst::DeviceMonitor::new();
+        monitor.add_filter(Some("Video/Source"), None);
+        monitor.start().map_err(|e| {
+            gst::glib::Error::new(
+                gst::CoreError::Failed,
+                &format!("Failed to start device monitor: {}", e),
+            )
+        })?;
+        let devices = monitor.devices();
+        monitor.stop();
+
+        Ok(devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| {
+                let name = device.display_name().to_string();
+                let device_id = device
+                    .properties()
+                    .and_then(|properties| {
+                        DEVICE_PROPERTIES
+                            .iter()
+                            .find_map(|key| properties.get::<String>(*key).ok())
+                    })
+                    .unwrap_or_else(|| name.clone());
+                CameraInfo {
+                    name,
+                    device: device_id,
+                    index,
+                    caps: device.caps().map(|c| caps_list(&c)).unwrap_or_default(),
+                    gst_device: Some(device.clone()),
+                }
+            })
+            .collect())
+    }
+}
+
+/// List the attached cameras.
+pub fn list_cameras() -> Result<Vec<CameraInfo>, gst::glib::Error> {
+    list_cameras_with(&GstDeviceProvider)
+}
+
+/// List the cameras reported by `provider`, dropping devices without any
+/// usable format.
+pub fn list_cameras_with(
+    provider: &dyn DeviceProvider,
+) -> Result<Vec<CameraInfo>, gst::glib::Error> {
+    Ok(provider
+        .devices()?
+        .into_iter()
+        .filter(|camera| !camera.caps.is_empty())
+        .collect())
+}
+
+/// Which camera a `Recorder` should open.
+#[derive(Debug, Clone)]
+pub enum DeviceSelector {
+    /// Exact device identifier, e.g. `/dev/video2`.
+    Path(String),
+    /// Position in the list returned by [`list_cameras`].
+    Index(usize),
+    /// Case-insensitive substring of the display name.
+    Name(String),
+    /// A device returned by [`list_cameras`].
+    Camera(CameraInfo),
+}
+
+impl DeviceSelector {
+    /// Find the selected camera among the attached ones.
+    pub fn resolve(&self) -> Result<CameraInfo, gst::glib::Error> {
+        self.resolve_with(&GstDeviceProvider)
+    }
+
+    /// Find the selected camera among the ones reported by `provider`.
+    pub fn resolve_with(
+        &self,
+        provider: &dyn DeviceProvider,
+    ) -> Result<CameraInfo, gst::glib::Error> {
+        let cameras = provider.devices()?;
+        let found = match self {
+            DeviceSelector::Path(path) => cameras.iter().find(|c| &c.device == path),
+            DeviceSelector::Index(index) => cameras.iter().find(|c| c.index == *index),
+            DeviceSelector::Name(name) => {
+                let name = name.to_lowercase();
+                cameras
+                    .iter()
+                    .find(|c| c.name.to_lowercase().contains(&name))
+            }
+            DeviceSelector::Camera(camera) => cameras.iter().find(|c| c.device == camera.device),
+        };
+        found.cloned().ok_or_else(|| {
+            let available = cameras
+                .iter()
+                .map(|c| format!("{}: {} ({})", c.index, c.name, c.device))
+                .collect::<Vec<_>>();
+            gst::glib::Error::new(
+                gst::ResourceError::NotFound,
+                &format!(
+                    "No camera matches {}; available: [{}]",
+                    self,
+                    available.join(", ")
+                ),
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelector::Path(path) => write!(f, "device '{}'", path),
+            DeviceSelector::Index(index) => write!(f, "index {}", index),
+            DeviceSelector::Name(name) => write!(f, "name '{}'", name),
+            DeviceSelector::Camera(camera) => write!(f, "device '{}'", camera.device),
+        }
+    }
+}
+
+impl<'source> FromPyObject<'source> for DeviceSelector {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        if let Ok(camera) = ob.extract::<CameraInfo>() {
+            Ok(DeviceSelector::Camera(camera))
+        } else if let Ok(index) = ob.extract::<usize>() {
+            Ok(DeviceSelector::Index(index))
+        } else if let Ok(selector) = ob.extract::<String>() {
+            // absolute paths and Windows device paths select exactly, anything
+            // else is matched against the display name
+            if std::path::Path::new(&selector).is_absolute() || selector.starts_with(r"\\?\") {
+                Ok(DeviceSelector::Path(selector))
+            } else {
+                Ok(DeviceSelector::Name(selector))
+            }
+        } else {
+            Err(pyo3::exceptions::PyTypeError::new_err(
+                "device must be a path, an index, a name or a CameraInfo",
+            ))
+        }
+    }
+}
+
+/// What to keep closest to when the requested mode isn't supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fallback {
+    Resolution,
+    Framerate,
+}
+
+/// The mode in `supported` closest to `requested`, preferring the same format.
+///
+/// MJPEG aliases (`MJPG`, `JPEG`, `image/jpeg`) count as the same format, and a
+/// request without a format prefers raw modes. An exact match is returned as is;
+/// otherwise modes are ranked by resolution then framerate, or the other way
+/// round, depending on `fallback`.
+pub fn nearest_caps(
+    requested: &CameraCaps,
+    supported: &[CameraCaps],
+    fallback: Fallback,
+) -> Option<CameraCaps> {
+    if let Some(exact) = supported.iter().find(|c| *c == requested) {
+        return Some(exact.clone());
+    }
+    let same_format = supported
+        .iter()
+        .filter(|c| {
+            if requested.format.is_empty() {
+                !c.is_mjpeg()
+            } else if requested.is_mjpeg() || c.is_mjpeg() {
+                requested.is_mjpeg() && c.is_mjpeg()
+            } else {
+                c.format.eq_ignore_ascii_case(&requested.format)
+            }
+        })
+        .collect::<Vec<_>>();
+    let candidates = if same_format.is_empty() {
+        supported.iter().collect()
+    } else {
+        same_format
+    };
+
+    let fps = |c: &CameraCaps| c.framerate_numerator as f64 / c.framerate_denominator.max(1) as f64;
+    let resolution_distance = |c: &CameraCaps| {
+        (c.width as f64 - requested.width as f64).abs()
+            + (c.height as f64 - requested.height as f64).abs()
+    };
+    let framerate_distance = |c: &CameraCaps| (fps(c) - fps(requested)).abs();
+    let key = |c: &CameraCaps| match fallback {
+        Fallback::Resolution => (resolution_distance(c), framerate_distance(c)),
+        Fallback::Framerate => (framerate_distance(c), resolution_distance(c)),
+    };
+
+    candidates
+        .into_iter()
+        .min_by(|a, b| {
+            key(a)
+                .partial_cmp(&key(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .cloned()
+}
+
+/// Expand device caps into one `CameraCaps` per width/height/framerate/format.
+///
+/// Raw video reports its pixel format (`YUY2`, `NV12`, ...), `image/jpeg` is
+/// reported as `MJPEG`. Lists of formats or framerates give one entry each,
+/// ranges are reduced to their largest value.
+pub fn caps_list(caps: &gst::Caps) -> Vec<CameraCaps> {
+    let mut list = Vec::new();
+    for structure in caps.iter() {
+        let formats = match structure.name().as_str() {
+            "video/x-raw" => raw_formats(structure),
+            "image/jpeg" => vec!["MJPEG".to_string()],
+            other => vec![other.to_string()],
+        };
+        let (Some(width), Some(height)) = (
+            int_value(structure, "width"),
+            int_value(structure, "height"),
+        ) else {
+            continue;
+        };
+        for format in &formats {
+            for framerate in framerates(structure) {
+                let caps = CameraCaps {
+                    width,
+                    height,
+                    framerate_numerator: framerate.numer(),
+                    framerate_denominator: framerate.denom(),
+                    format: format.clone(),
+                };
+                if !list.iter().any(|c: &CameraCaps| c == &caps) {
+                    list.push(caps);
+                }
+            }
+        }
+    }
+    list
+}
+
+/// The raw format, or each format of a list.
+fn raw_formats(structure: &gst::StructureRef) -> Vec<String> {
+    let Ok(value) = structure.value("format") else {
+        return Vec::new();
+    };
+    if let Ok(format) = value.get::<String>() {
+        vec![format]
+    } else if let Ok(list) = value.get::<gst::List>() {
+        list.iter().filter_map(|v| v.get::<String>().ok()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn int_value(structure: &gst::StructureRef, field: &str) -> Option<i32> {
+    let value = structure.value(field).ok()?;
+    value
+        .get::<i32>()
+        .ok()
+        .or_else(|| value.get::<gst::IntRange<i32>>().ok().map(|r| r.max()))
+}
+
+fn framerates(structure: &gst::StructureRef) -> Vec<gst::Fraction> {
+    let Ok(value) = structure.value("framerate") else {
+        return Vec::new();
+    };
+    if let Ok(framerate) = value.get::<gst::Fraction>() {
+        vec![framerate]
+    } else if let Ok(list) = value.get::<gst::List>() {
+        list.iter()
+            .filter_map(|v| v.get::<gst::Fraction>().ok())
+            .collect()
+    } else if let Ok(range) = value.get::<gst::FractionRange>() {
+        vec![range.max()]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(width: i32, height: i32, fps: i32, format: &str) -> CameraCaps {
+        CameraCaps {
+            width,
+            height,
+            framerate_numerator: fps,
+            framerate_denominator: 1,
+            format: format.to_string(),
+        }
+    }
+
+    fn camera(index: usize, name: &str, device: &str, caps: Vec<CameraCaps>) -> CameraInfo {
+        CameraInfo {
+            name: name.to_string(),
+            device: device.to_string(),
+            index,
+            caps,
+            gst_device: None,
+        }
+    }
+
+    struct FakeProvider(Vec<CameraInfo>);
+
+    impl DeviceProvider for FakeProvider {
+        fn devices(&self) -> Result<Vec<CameraInfo>, gst::glib::Error> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn provider() -> FakeProvider {
+        FakeProvider(vec![
+            camera(
+                0,
+                "Integrated Camera",
+                "/dev/video0",
+                vec![caps(1280, 720, 30, "MJPEG")],
+            ),
+            // metadata node without formats
+            camera(1, "Integrated Camera", "/dev/video1", Vec::new()),
+            camera(
+                2,
+                "HD Pro Webcam C920",
+                "/dev/video2",
+                vec![caps(640, 480, 30, "YUY2"), caps(1920, 1080, 30, "MJPEG")],
+            ),
+        ])
+    }
+
+    #[test]
+    fn lists_cameras_with_formats() {
+        let cameras = list_cameras_with(&provider()).unwrap();
+        let devices = cameras
+            .iter()
+            .map(|c| c.device.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(devices, ["/dev/video0", "/dev/video2"]);
+    }
+
+    #[test]
+    fn resolves_by_index_name_and_path() {
+        let provider = provider();
+        let resolve = |selector: DeviceSelector| selector.resolve_with(&provider).unwrap().device;
+
+        assert_eq!(resolve(DeviceSelector::Index(2)), "/dev/video2");
+        assert_eq!(resolve(DeviceSelector::Name("c920".into())), "/dev/video2");
+        assert_eq!(
+            resolve(DeviceSelector::Path("/dev/video0".into())),
+            "/dev/video0"
+        );
+        let listed = camera(7, "renamed", "/dev/video2", Vec::new());
+        assert_eq!(resolve(DeviceSelector::Camera(listed)), "/dev/video2");
+    }
+
+    #[test]
+    fn reports_the_available_cameras_when_nothing_matches() {
+        let error = DeviceSelector::Name("Brio".into())
+            .resolve_with(&provider())
+            .unwrap_err();
+        assert!(error.matches(gst::ResourceError::NotFound));
+        let message = error.message();
+        assert!(
+            message.contains("No camera matches name 'Brio'"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("2: HD Pro Webcam C920 (/dev/video2)"),
+            "{}",
+            message
+        );
+
+        assert!(DeviceSelector::Index(5).resolve_with(&provider()).is_err());
+        assert!(DeviceSelector::Path("/dev/video9".into())
+            .resolve_with(&provider())
+            .is_err());
+    }
+
+    #[test]
+    fn expands_device_caps() {
+        gst::init().unwrap();
+        let device_caps = [
+            gst::Structure::builder("video/x-raw")
+                .field("format", "YUY2")
+                .field("width", 640)
+                .field("height", 480)
+                .field(
+                    "framerate",
+                    gst::List::new([gst::Fraction::new(30, 1), gst::Fraction::new(15, 1)]),
+                )
+                .build(),
+            gst::Structure::builder("image/jpeg")
+                .field("width", gst::IntRange::new(320, 1920))
+                .field("height", 1080)
+                .field(
+                    "framerate",
+                    gst::FractionRange::new(gst::Fraction::new(1, 1), gst::Fraction::new(60, 1)),
+                )
+                .build(),
+            gst::Structure::builder("video/x-raw")
+                .field("format", gst::List::new(["NV12", "I420"]))
+                .field("width", 320)
+                .field("height", 240)
+                .field("framerate", gst::Fraction::new(30, 1))
+                .build(),
+        ]
+        .into_iter()
+        .collect::<gst::Caps>();
+
+        assert_eq!(
+            caps_list(&device_caps),
+            [
+                caps(640, 480, 30, "YUY2"),
+                caps(640, 480, 15, "YUY2"),
+                caps(1920, 1080, 60, "MJPEG"),
+                caps(320, 240, 30, "NV12"),
+                caps(320, 240, 30, "I420"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_cameras_reporting_format_lists() {
+        gst::init().unwrap();
+        let device_caps = gst::Caps::builder("video/x-raw")
+            .field("format", gst::List::new(["NV12", "I420"]))
+            .field("width", 1280)
+            .field("height", 720)
+            .field("framerate", gst::Fraction::new(30, 1))
+            .build();
+        let mut provider = provider();
+        provider.0.push(camera(
+            3,
+            "Virtual Camera",
+            "/dev/video3",
+            caps_list(&device_caps),
+        ));
+
+        let cameras = list_cameras_with(&provider).unwrap();
+        let virtual_camera = cameras.iter().find(|c| c.device == "/dev/video3").unwrap();
+        assert_eq!(
+            virtual_camera.caps,
+            [caps(1280, 720, 30, "NV12"), caps(1280, 720, 30, "I420")]
+        );
+        assert_eq!(
+            nearest_caps(
+                &caps(1280, 720, 30, "I420"),
+                &virtual_camera.caps,
+                Fallback::Framerate
+            ),
+            Some(caps(1280, 720, 30, "I420"))
+        );
+    }
+
+    #[test]
+    fn keeps_an_exact_match() {
+        let supported = [caps(640, 480, 30, "YUY2"), caps(1280, 720, 30, "YUY2")];
+        assert_eq!(
+            nearest_caps(&supported[1], &supported, Fallback::Framerate),
+            Some(supported[1].clone())
+        );
+    }
+
+    #[test]
+    fn falls_back_by_resolution_or_framerate() {
+        let supported = [
+            caps(1280, 720, 10, "YUY2"),
+            caps(640, 480, 30, "YUY2"),
+            caps(1280, 720, 30, "MJPEG"),
+        ];
+        let requested = caps(1280, 720, 30, "YUY2");
+
+        // the same format wins over an exact MJPEG mode
+        assert_eq!(
+            nearest_caps(&requested, &supported, Fallback::Resolution),
+            Some(caps(1280, 720, 10, "YUY2"))
+        );
+        assert_eq!(
+            nearest_caps(&requested, &supported, Fallback::Framerate),
+            Some(caps(640, 480, 30, "YUY2"))
+        );
+        // without the format, any mode is considered
+        assert_eq!(
+            nearest_caps(
+                &caps(1280, 720, 30, "NV12"),
+                &supported,
+                Fallback::Resolution
+            ),
+            Some(caps(1280, 720, 30, "MJPEG"))
+        );
+        assert_eq!(nearest_caps(&requested, &[], Fallback::Resolution), None);
+    }
+
+    #[test]
+    fn treats_mjpeg_aliases_as_one_format() {
+        let supported = [caps(1280, 720, 30, "YUY2"), caps(640, 480, 30, "MJPEG")];
+        for alias in ["MJPG", "mjpeg", "jpeg", "image/jpeg"] {
+            assert_eq!(
+                nearest_caps(
+                    &caps(1280, 720, 30, alias),
+                    &supported,
+                    Fallback::Resolution
+                ),
+                Some(caps(640, 480, 30, "MJPEG")),
+                "{}",
+                alias
+            );
+        }
+    }
+
+    #[test]
+    fn prefers_raw_modes_without_a_format() {
+        let supported = [caps(1920, 1080, 30, "MJPEG"), caps(1280, 720, 10, "NV12")];
+        assert_eq!(
+            nearest_caps(&caps(1920, 1080, 30, ""), &supported, Fallback::Framerate),
+            Some(caps(1280, 720, 10, "NV12"))
+        );
+        // unless the camera only has MJPEG
+        assert_eq!(
+            nearest_caps(
+                &caps(1920, 1080, 30, ""),
+                &supported[..1],
+                Fallback::Framerate
+            ),
+            Some(caps(1920, 1080, 30, "MJPEG"))
+        );
+    }
+}
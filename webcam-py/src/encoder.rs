@@ -0,0 +1,680 @@
+//! Video encoder selection and codec settings.
+
+use pyo3::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    H264,
+    H265,
+    Vp8,
+    Vp9,
+    Av1,
+    /// Camera MJPEG stored as is, or `jpegenc` for raw sources.
+    Mjpeg,
+    /// Lossless FFV1 through gst-libav.
+    Ffv1,
+}
+
+/// Rate control; bitrates are in kbit/s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateControl {
+    Cbr(u32),
+    Vbr(u32),
+    /// Constant rate factor (constant quality).
+    Crf(u32),
+    /// Fixed quantizer.
+    Qp(u32),
+}
+
+// encoders tried in order, with the plugin set that provides them
+const H264_ENCODERS: &[(&str, &str)] = &[("x264enc", "gst-plugins-ugly")];
+const H265_ENCODERS: &[(&str, &str)] = &[("x265enc", "gst-plugins-bad")];
+const VP8_ENCODERS: &[(&str, &str)] = &[("vp8enc", "gst-plugins-good")];
+const VP9_ENCODERS: &[(&str, &str)] = &[("vp9enc", "gst-plugins-good")];
+const AV1_ENCODERS: &[(&str, &str)] = &[
+    ("svtav1enc", "gst-plugins-bad"),
+    ("rav1enc", "gst-plugins-rs"),
+    ("av1enc", "gst-plugins-bad"),
+];
+const MJPEG_ENCODERS: &[(&str, &str)] = &[("jpegenc", "gst-plugins-good")];
+const FFV1_ENCODERS: &[(&str, &str)] = &[("avenc_ffv1", "gst-libav")];
+
+impl Codec {
+    fn encoders(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Codec::H264 => H264_ENCODERS,
+            Codec::H265 => H265_ENCODERS,
+            Codec::Vp8 => VP8_ENCODERS,
+            Codec::Vp9 => VP9_ENCODERS,
+            Codec::Av1 => AV1_ENCODERS,
+            Codec::Mjpeg => MJPEG_ENCODERS,
+            Codec::Ffv1 => FFV1_ENCODERS,
+        }
+    }
+
+    /// Name of the first installed encoder element for this codec.
+    pub fn encoder_factory(&self) -> Option<&'static str> {
+        self.encoders()
+            .iter()
+            .map(|(name, _)| *name)
+            .find(|name| gst::ElementFactory::find(name).is_some())
+    }
+
+    pub fn is_available(&self) -> bool {
+        gst::init().is_ok() && self.encoder_factory().is_some()
+    }
+
+    fn parser_factory(&self) -> Option<&'static str> {
+        match self {
+            Codec::H264 => Some("h264parse"),
+            Codec::H265 => Some("h265parse"),
+            Codec::Av1 => Some("av1parse"),
+            Codec::Mjpeg => Some("jpegparse"),
+            Codec::Vp8 | Codec::Vp9 | Codec::Ffv1 => None,
+        }
+    }
+
+    fn profile_caps(&self) -> Option<&'static str> {
+        match self {
+            Codec::H264 => Some("video/x-h264"),
+            Codec::H265 => Some("video/x-h265"),
+            Codec::Av1 => Some("video/x-av1"),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Codec::H264 => "h264",
+            Codec::H265 => "h265",
+            Codec::Vp8 => "vp8",
+            Codec::Vp9 => "vp9",
+            Codec::Av1 => "av1",
+            Codec::Mjpeg => "mjpeg",
+            Codec::Ffv1 => "ffv1",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "h264" | "x264" | "avc" => Ok(Codec::H264),
+            "h265" | "x265" | "hevc" => Ok(Codec::H265),
+            "vp8" => Ok(Codec::Vp8),
+            "vp9" => Ok(Codec::Vp9),
+            "av1" => Ok(Codec::Av1),
+            "mjpeg" | "mjpg" | "jpeg" => Ok(Codec::Mjpeg),
+            "ffv1" => Ok(Codec::Ffv1),
+            _ => Err(format!("Unknown codec '{}'", s)),
+        }
+    }
+}
+
+/// How the recording branch encodes video.
+///
+/// The default matches the original behaviour: x264 with the `ultrafast`
+/// preset and `zerolatency` tune.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass(name = "EncoderConfig")]
+pub struct EncoderConfig {
+    pub codec: Codec,
+    pub rate_control: Option<RateControl>,
+    /// Maximum distance between keyframes, in frames.
+    pub keyframe_interval: Option<u32>,
+    /// Encoder speed preset, e.g. `ultrafast` for x264 or `8` for SVT-AV1.
+    pub preset: Option<String>,
+    pub tune: Option<String>,
+    /// Codec profile, e.g. `high` for H.264 or `main` for H.265.
+    pub profile: Option<String>,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        EncoderConfig {
+            codec: Codec::H264,
+            rate_control: None,
+            keyframe_interval: None,
+            preset: Some("ultrafast".to_string()),
+            tune: Some("zerolatency".to_string()),
+            profile: None,
+        }
+    }
+}
+
+/// Elements between the raw video and the muxer, in link order.
+pub(crate) struct EncoderElements {
+    pub(crate) encoder: Option<gst::Element>,
+    pub(crate) profile_filter: Option<gst::Element>,
+    pub(crate) parser: Option<gst::Element>,
+}
+
+fn not_supported(codec: Codec, what: &str) -> gst::glib::Error {
+    gst::glib::Error::new(
+        gst::CoreError::NotImplemented,
+        &format!("{} is not supported for {}", what, codec),
+    )
+}
+
+fn set(
+    element: &gst::Element,
+    property: &str,
+    value: impl ToString,
+) -> Result<(), gst::glib::Error> {
+    if element.find_property(property).is_none() {
+        return Err(gst::glib::Error::new(
+            gst::CoreError::NotImplemented,
+            &format!(
+                "{} has no property '{}'",
+                element
+                    .factory()
+                    .map(|f| f.name().to_string())
+                    .unwrap_or_default(),
+                property
+            ),
+        ));
+    }
+    element.set_property_from_str(property, &value.to_string());
+    Ok(())
+}
+
+/// Encoder properties as strings, in the order they are set.
+#[derive(Default)]
+struct Settings(Vec<(&'static str, String)>);
+
+impl Settings {
+    fn set(&mut self, property: &'static str, value: impl ToString) {
+        self.0.push((property, value.to_string()));
+    }
+
+    fn optional(&mut self, property: &'static str, value: Option<impl ToString>) {
+        if let Some(value) = value {
+            self.set(property, value);
+        }
+    }
+}
+
+impl EncoderConfig {
+    pub fn new(codec: Codec) -> Self {
+        EncoderConfig {
+            codec,
+            ..EncoderConfig::default()
+        }
+        .without_x264_defaults()
+    }
+
+    // the default preset/tune only make sense for x264
+    fn without_x264_defaults(mut self) -> Self {
+        if self.codec != Codec::H264 {
+            self.preset = None;
+            self.tune = None;
+        }
+        self
+    }
+
+    /// Check that an encoder for the codec is installed and the settings apply
+    /// to it, without building a pipeline.
+    pub fn validate(&self) -> Result<(), gst::glib::Error> {
+        self.build(false).map(|_| ())
+    }
+
+    /// Create and configure the encoder elements.
+    ///
+    /// With `passthrough`, MJPEG frames from the camera are stored without
+    /// re-encoding and only a parser is created.
+    pub(crate) fn build(&self, passthrough: bool) -> Result<EncoderElements, gst::glib::Error> {
+        gst::init()?;
+
+        let parser = match self.codec.parser_factory() {
+            Some(name) => Some(gst::ElementFactory::make(name).build().map_err(|_| {
+                gst::glib::Error::new(
+                    gst::CoreError::MissingPlugin,
+                    &format!("{} is not installed", name),
+                )
+            })?),
+            None => None,
+        };
+        if passthrough && self.codec == Codec::Mjpeg {
+            return Ok(EncoderElements {
+                encoder: None,
+                profile_filter: None,
+                parser,
+            });
+        }
+
+        let factory = self.codec.encoder_factory().ok_or_else(|| {
+            let missing = self
+                .codec
+                .encoders()
+                .iter()
+                .map(|(name, plugins)| format!("{} ({})", name, plugins))
+                .collect::<Vec<_>>();
+            gst::glib::Error::new(
+                gst::CoreError::MissingPlugin,
+                &format!(
+                    "No {} encoder is installed; install one of {}",
+                    self.codec,
+                    missing.join(", ")
+                ),
+            )
+        })?;
+        let encoder = gst::ElementFactory::make(factory)
+            .build()
+            .map_err(|e| gst::glib::Error::new(gst::CoreError::MissingPlugin, &e.to_string()))?;
+        for (property, value) in self.settings(factory)? {
+            set(&encoder, property, value)?;
+        }
+
+        let profile_filter = match (&self.profile, self.codec.profile_caps()) {
+            (None, _) => None,
+            (Some(profile), Some(media_type)) => {
+                let filter = gst::ElementFactory::make("capsfilter").build().unwrap();
+                filter.set_property(
+                    "caps",
+                    gst::Caps::builder(media_type)
+                        .field("profile", profile)
+                        .build(),
+                );
+                Some(filter)
+            }
+            (Some(_), None) => return Err(not_supported(self.codec, "A profile")),
+        };
+
+        Ok(EncoderElements {
+            encoder: Some(encoder),
+            profile_filter,
+            parser,
+        })
+    }
+
+    /// Encoder properties for the settings, in the order they are applied.
+    fn settings(&self, factory: &str) -> Result<Vec<(&'static str, String)>, gst::glib::Error> {
+        let mut settings = Settings::default();
+        match factory {
+            "x264enc" => {
+                match self.rate_control {
+                    Some(RateControl::Cbr(kbps)) => {
+                        settings.set("pass", "cbr");
+                        settings.set("bitrate", kbps);
+                    }
+                    // quality-based VBR, in qual mode the bitrate is the upper cap
+                    Some(RateControl::Vbr(kbps)) => {
+                        settings.set("pass", "qual");
+                        settings.set("bitrate", kbps);
+                    }
+                    Some(RateControl::Crf(crf)) => {
+                        settings.set("pass", "qual");
+                        settings.set("quantizer", crf);
+                        // the bitrate only caps qual mode; use the maximum, so
+                        // quality alone decides
+                        settings.set("bitrate", 2048000);
+                    }
+                    Some(RateControl::Qp(qp)) => {
+                        settings.set("pass", "quant");
+                        settings.set("quantizer", qp);
+                    }
+                    None => {}
+                }
+                settings.optional("key-int-max", self.keyframe_interval);
+                settings.optional("speed-preset", self.preset.as_ref());
+                settings.optional("tune", self.tune.as_ref());
+            }
+            "x265enc" => {
+                match self.rate_control {
+                    Some(RateControl::Cbr(kbps)) => {
+                        settings.set("bitrate", kbps);
+                        settings.set(
+                            "option-string",
+                            format!("vbv-maxrate={}:vbv-bufsize={}", kbps, kbps),
+                        );
+                    }
+                    Some(RateControl::Vbr(kbps)) => settings.set("bitrate", kbps),
+                    Some(RateControl::Crf(crf)) => {
+                        settings.set("option-string", format!("crf={}", crf))
+                    }
+                    Some(RateControl::Qp(qp)) => settings.set("qp", qp),
+                    None => {}
+                }
+                settings.optional("key-int-max", self.keyframe_interval);
+                settings.optional("speed-preset", self.preset.as_ref());
+                settings.optional("tune", self.tune.as_ref());
+            }
+            "vp8enc" | "vp9enc" | "av1enc" => {
+                // libvpx and libaom share their rate control properties
+                let bitrate = |kbps: u32| match factory {
+                    "av1enc" => kbps,
+                    _ => kbps * 1000,
+                };
+                match self.rate_control {
+                    Some(RateControl::Cbr(kbps)) => {
+                        settings.set("end-usage", "cbr");
+                        settings.set("target-bitrate", bitrate(kbps));
+                    }
+                    Some(RateControl::Vbr(kbps)) => {
+                        settings.set("end-usage", "vbr");
+                        settings.set("target-bitrate", bitrate(kbps));
+                    }
+                    Some(RateControl::Crf(crf)) => {
+                        settings.set("end-usage", "q");
+                        settings.set("cq-level", crf);
+                    }
+                    Some(RateControl::Qp(qp)) => {
+                        settings.set("min-quantizer", qp);
+                        settings.set("max-quantizer", qp);
+                    }
+                    None => {}
+                }
+                settings.optional("keyframe-max-dist", self.keyframe_interval);
+                // speed presets are libvpx/libaom cpu-used levels
+                settings.optional("cpu-used", self.preset.as_ref());
+                if self.tune.is_some() {
+                    return Err(not_supported(self.codec, "tune"));
+                }
+            }
+            "svtav1enc" => {
+                match self.rate_control {
+                    Some(RateControl::Cbr(kbps)) | Some(RateControl::Vbr(kbps)) => {
+                        settings.set("target-bitrate", kbps)
+                    }
+                    Some(RateControl::Crf(crf)) => settings.set("crf", crf),
+                    Some(RateControl::Qp(_)) => {
+                        return Err(not_supported(self.codec, "QP rate control"))
+                    }
+                    None => {}
+                }
+                settings.optional("intra-period-length", self.keyframe_interval);
+                settings.optional("preset", self.preset.as_ref());
+                if self.tune.is_some() {
+                    return Err(not_supported(self.codec, "tune"));
+                }
+            }
+            "rav1enc" => {
+                match self.rate_control {
+                    Some(RateControl::Cbr(kbps)) | Some(RateControl::Vbr(kbps)) => {
+                        settings.set("bitrate", kbps * 1000)
+                    }
+                    Some(RateControl::Crf(q)) | Some(RateControl::Qp(q)) => {
+                        settings.set("quantizer", q)
+                    }
+                    None => {}
+                }
+                settings.optional("max-key-frame-interval", self.keyframe_interval);
+                settings.optional("speed-preset", self.preset.as_ref());
+                if self.tune.is_some() {
+                    return Err(not_supported(self.codec, "tune"));
+                }
+            }
+            "jpegenc" => {
+                match self.rate_control {
+                    // 0-100, higher is better
+                    Some(RateControl::Crf(quality)) | Some(RateControl::Qp(quality)) => {
+                        settings.set("quality", quality)
+                    }
+                    Some(_) => return Err(not_supported(self.codec, "Bitrate control")),
+                    None => {}
+                }
+                if self.preset.is_some() || self.tune.is_some() {
+                    return Err(not_supported(self.codec, "A preset or tune"));
+                }
+            }
+            "avenc_ffv1" => {
+                if self.rate_control.is_some() {
+                    return Err(not_supported(self.codec, "Rate control"));
+                }
+                settings.optional("gop-size", self.keyframe_interval);
+                if self.preset.is_some() || self.tune.is_some() {
+                    return Err(not_supported(self.codec, "A preset or tune"));
+                }
+            }
+            _ => {
+                return Err(gst::glib::Error::new(
+                    gst::CoreError::NotImplemented,
+                    &format!("No settings for encoder {}", factory),
+                ))
+            }
+        }
+        Ok(settings.0)
+    }
+}
+
+#[pymethods]
+impl EncoderConfig {
+    #[new]
+    #[pyo3(signature = (codec = "h264", cbr = None, vbr = None, crf = None, qp = None, keyframe_interval = None, preset = None, tune = None, profile = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn __new__(
+        codec: &str,
+        cbr: Option<u32>,
+        vbr: Option<u32>,
+        crf: Option<u32>,
+        qp: Option<u32>,
+        keyframe_interval: Option<u32>,
+        preset: Option<String>,
+        tune: Option<String>,
+        profile: Option<String>,
+    ) -> PyResult<Self> {
+        let codec = codec
+            .parse::<Codec>()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let rate_controls = [
+            cbr.map(RateControl::Cbr),
+            vbr.map(RateControl::Vbr),
+            crf.map(RateControl::Crf),
+            qp.map(RateControl::Qp),
+        ];
+        let mut rate_controls = rate_controls.into_iter().flatten();
+        let rate_control = rate_controls.next();
+        if rate_controls.next().is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Only one of cbr, vbr, crf and qp can be set",
+            ));
+        }
+
+        let mut config = EncoderConfig::new(codec);
+        config.rate_control = rate_control;
+        config.keyframe_interval = keyframe_interval;
+        config.preset = preset.or(config.preset);
+        config.tune = tune.or(config.tune);
+        config.profile = profile;
+        Ok(config)
+    }
+
+    #[getter(codec)]
+    fn py_codec(&self) -> String {
+        self.codec.to_string()
+    }
+
+    /// Raise if no encoder for the codec is installed or a setting doesn't apply.
+    #[pyo3(name = "validate")]
+    fn py_validate(&self) -> PyResult<()> {
+        self.validate().map_err(|e| {
+            pyo3::exceptions::PyRuntimeError::new_err(format!("Invalid encoder config: {}", e))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("EncoderConfig({:?})", self)
+    }
+}
+
+/// Codecs with an installed encoder.
+pub fn available_codecs() -> Vec<Codec> {
+    [
+        Codec::H264,
+        Codec::H265,
+        Codec::Vp8,
+        Codec::Vp9,
+        Codec::Av1,
+        Codec::Mjpeg,
+        Codec::Ffv1,
+    ]
+    .into_iter()
+    .filter(Codec::is_available)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(codec: Codec, rate_control: Option<RateControl>) -> EncoderConfig {
+        EncoderConfig {
+            rate_control,
+            ..EncoderConfig::new(codec)
+        }
+    }
+
+    fn settings(config: &EncoderConfig, factory: &str) -> Vec<(&'static str, String)> {
+        config.settings(factory).unwrap()
+    }
+
+    fn pairs(pairs: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        pairs
+            .iter()
+            .map(|(property, value)| (*property, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_codec_names_and_aliases() {
+        for (name, codec) in [
+            ("h264", Codec::H264),
+            ("AVC", Codec::H264),
+            ("x265", Codec::H265),
+            ("hevc", Codec::H265),
+            ("vp8", Codec::Vp8),
+            ("VP9", Codec::Vp9),
+            ("av1", Codec::Av1),
+            ("mjpg", Codec::Mjpeg),
+            ("jpeg", Codec::Mjpeg),
+            ("ffv1", Codec::Ffv1),
+        ] {
+            assert_eq!(name.parse::<Codec>(), Ok(codec), "{}", name);
+        }
+        assert_eq!(
+            "theora".parse::<Codec>(),
+            Err("Unknown codec 'theora'".to_string())
+        );
+    }
+
+    #[test]
+    fn maps_x264_rate_control() {
+        let defaults = [("speed-preset", "ultrafast"), ("tune", "zerolatency")];
+        for (rate_control, expected) in [
+            (
+                RateControl::Cbr(4000),
+                &[("pass", "cbr"), ("bitrate", "4000")][..],
+            ),
+            (
+                RateControl::Vbr(4000),
+                &[("pass", "qual"), ("bitrate", "4000")][..],
+            ),
+            (
+                RateControl::Crf(23),
+                &[
+                    ("pass", "qual"),
+                    ("quantizer", "23"),
+                    ("bitrate", "2048000"),
+                ][..],
+            ),
+            (
+                RateControl::Qp(20),
+                &[("pass", "quant"), ("quantizer", "20")][..],
+            ),
+        ] {
+            let mut expected = expected.to_vec();
+            expected.extend(defaults);
+            assert_eq!(
+                settings(&config(Codec::H264, Some(rate_control)), "x264enc"),
+                pairs(&expected),
+                "{:?}",
+                rate_control
+            );
+        }
+    }
+
+    #[test]
+    fn maps_keyframes_and_presets() {
+        let config = EncoderConfig {
+            keyframe_interval: Some(60),
+            preset: Some("8".to_string()),
+            ..EncoderConfig::new(Codec::Av1)
+        };
+        assert_eq!(
+            settings(&config, "svtav1enc"),
+            pairs(&[("intra-period-length", "60"), ("preset", "8")])
+        );
+        assert_eq!(
+            settings(&config, "rav1enc"),
+            pairs(&[("max-key-frame-interval", "60"), ("speed-preset", "8")])
+        );
+        assert_eq!(
+            settings(&config, "av1enc"),
+            pairs(&[("keyframe-max-dist", "60"), ("cpu-used", "8")])
+        );
+    }
+
+    #[test]
+    fn converts_bitrates_to_encoder_units() {
+        // libvpx takes bit/s, libaom kbit/s
+        assert_eq!(
+            settings(&config(Codec::Vp9, Some(RateControl::Vbr(2000))), "vp9enc"),
+            pairs(&[("end-usage", "vbr"), ("target-bitrate", "2000000")])
+        );
+        assert_eq!(
+            settings(&config(Codec::Av1, Some(RateControl::Cbr(2000))), "av1enc"),
+            pairs(&[("end-usage", "cbr"), ("target-bitrate", "2000")])
+        );
+        assert_eq!(
+            settings(&config(Codec::Av1, Some(RateControl::Cbr(2000))), "rav1enc"),
+            pairs(&[("bitrate", "2000000")])
+        );
+        assert_eq!(
+            settings(
+                &config(Codec::H265, Some(RateControl::Cbr(3000))),
+                "x265enc"
+            ),
+            pairs(&[
+                ("bitrate", "3000"),
+                ("option-string", "vbv-maxrate=3000:vbv-bufsize=3000")
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_settings() {
+        for (config, factory) in [
+            (config(Codec::Av1, Some(RateControl::Qp(30))), "svtav1enc"),
+            (
+                config(Codec::Mjpeg, Some(RateControl::Cbr(8000))),
+                "jpegenc",
+            ),
+            (config(Codec::Ffv1, Some(RateControl::Crf(0))), "avenc_ffv1"),
+            (
+                EncoderConfig {
+                    tune: Some("psnr".to_string()),
+                    ..EncoderConfig::new(Codec::Vp8)
+                },
+                "vp8enc",
+            ),
+            (EncoderConfig::new(Codec::H264), "openh264enc"),
+        ] {
+            let error = config.settings(factory).unwrap_err();
+            assert!(
+                error.matches(gst::CoreError::NotImplemented),
+                "{}: {}",
+                factory,
+                error
+            );
+        }
+        assert_eq!(
+            settings(&config(Codec::Mjpeg, Some(RateControl::Crf(85))), "jpegenc"),
+            pairs(&[("quality", "85")])
+        );
+    }
+}
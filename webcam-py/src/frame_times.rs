@@ -0,0 +1,204 @@
+//! Per-frame timestamp sidecar, for aligning video with other recordings.
+//!
+//! One row per captured frame with the buffer PTS, the pipeline running time,
+//! and the system wall clock and monotonic clock (`CLOCK_MONOTONIC`, which LSL's
+//! `local_clock` uses on Linux) at capture, all in seconds. Tab separated, or comma
+//! separated if the path ends in `.csv`. Rows are flushed to disk every
+//! second, so a crash loses at most the last second of timestamps.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::CameraCaps;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) struct FrameLog {
+    writer: BufWriter<File>,
+    separator: &'static str,
+    /// Nominal frame duration, if the framerate is known.
+    interval: Option<f64>,
+    last_pts: Option<f64>,
+    last_flush: Instant,
+}
+
+impl FrameLog {
+    pub(crate) fn create(path: &Path, caps: &CameraCaps) -> std::io::Result<Self> {
+        let separator = match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => ",",
+            _ => "\t",
+        };
+        let interval = (caps.framerate_numerator > 0 && caps.framerate_denominator > 0)
+            .then(|| caps.framerate_denominator as f64 / caps.framerate_numerator as f64);
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "{}",
+            [
+                "frame",
+                "pts",
+                "running_time",
+                "wall_clock",
+                "monotonic",
+                "flag"
+            ]
+            .join(separator)
+        )?;
+        Ok(FrameLog {
+            writer,
+            separator,
+            interval,
+            last_pts: None,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Append the row of frame `index`.
+    ///
+    /// The flag is `dropped` if frames are missing before this one, and
+    /// `duplicated` if it doesn't advance the PTS. `running_time` is the PTS
+    /// converted with the segment of the pad the frame was seen on. `age` is
+    /// how long ago the frame was captured, in seconds; it is subtracted from
+    /// both clocks.
+    pub(crate) fn log(
+        &mut self,
+        index: usize,
+        pts: gst::ClockTime,
+        running_time: Option<gst::ClockTime>,
+        age: f64,
+    ) -> std::io::Result<()> {
+        let wall_clock = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default()
+            - age;
+        let monotonic = gst::glib::monotonic_time() as f64 / 1e6 - age;
+        let pts = pts.seconds_f64();
+
+        let flag = match (self.last_pts, self.interval) {
+            (Some(last), _) if pts <= last => "duplicated",
+            (Some(last), Some(interval)) if pts - last > 1.5 * interval => "dropped",
+            _ => "ok",
+        };
+        self.last_pts = Some(pts);
+
+        let running_time = running_time
+            .map(|t| format!("{:.9}", t.seconds_f64()))
+            .unwrap_or_default();
+        writeln!(
+            self.writer,
+            "{}",
+            [
+                index.to_string(),
+                format!("{:.9}", pts),
+                running_time,
+                format!("{:.6}", wall_clock),
+                format!("{:.6}", monotonic),
+                flag.to_string(),
+            ]
+            .join(self.separator)
+        )?;
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.last_flush = Instant::now();
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(fps: i32) -> CameraCaps {
+        CameraCaps {
+            width: 640,
+            height: 480,
+            framerate_numerator: fps,
+            framerate_denominator: 1,
+            format: "YUY2".to_string(),
+        }
+    }
+
+    /// Log frames with the given PTS in milliseconds and return the flag column.
+    fn flags(name: &str, fps: i32, pts: &[u64]) -> Vec<String> {
+        let path =
+            std::env::temp_dir().join(format!("webcam-py-{}-{}.tsv", name, std::process::id()));
+        let mut log = FrameLog::create(&path, &caps(fps)).unwrap();
+        for (index, &pts) in pts.iter().enumerate() {
+            log.log(index, gst::ClockTime::from_mseconds(pts), None, 0.0)
+                .unwrap();
+        }
+        log.flush().unwrap();
+        drop(log);
+
+        let rows = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        rows.lines()
+            .skip(1)
+            .map(|row| row.split('\t').last().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn flags_steady_frames_ok() {
+        assert_eq!(flags("steady", 10, &[0, 100, 200, 300]), ["ok"; 4]);
+        // jitter within half a frame is not a drop
+        assert_eq!(flags("jitter", 10, &[0, 140, 200]), ["ok"; 3]);
+    }
+
+    #[test]
+    fn flags_dropped_frames() {
+        assert_eq!(
+            flags("dropped", 10, &[0, 100, 300, 400, 1000]),
+            ["ok", "ok", "dropped", "ok", "dropped"]
+        );
+    }
+
+    #[test]
+    fn flags_duplicated_frames() {
+        assert_eq!(
+            flags("duplicated", 10, &[0, 100, 100, 50, 200]),
+            ["ok", "ok", "duplicated", "duplicated", "ok"]
+        );
+    }
+
+    #[test]
+    fn cannot_detect_drops_without_a_framerate() {
+        assert_eq!(
+            flags("no-framerate", 0, &[0, 100, 1000, 1000]),
+            ["ok", "ok", "ok", "duplicated"]
+        );
+    }
+
+    #[test]
+    fn moves_clocks_back_by_the_age() {
+        let path = std::env::temp_dir().join(format!("webcam-py-age-{}.csv", std::process::id()));
+        let mut log = FrameLog::create(&path, &caps(30)).unwrap();
+        let before = gst::glib::monotonic_time() as f64 / 1e6;
+        log.log(0, gst::ClockTime::ZERO, Some(gst::ClockTime::ZERO), 5.0)
+            .unwrap();
+        log.flush().unwrap();
+        drop(log);
+
+        let rows = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let row: Vec<&str> = rows.lines().nth(1).unwrap().split(',').collect();
+        assert_eq!(row[..3], ["0", "0.000000000", "0.000000000"]);
+        let monotonic: f64 = row[4].parse().unwrap();
+        assert!(
+            monotonic < before - 4.0 && monotonic > before - 6.0,
+            "{}",
+            monotonic
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! Checksum sidecars for finished video files.
+//!
+//! The format is defined in the `recording-sidecars` crate shared with
+//! lsl-recorder: `<file>.sha256` in the format of `sha256sum`, and `<file>.json`
+//! with the size, the checksum and a summary of the recorded streams.
+//! `lsl_recorder.verify_integrity()` checks either kind.
+
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::CameraCaps;
+
+/// Write the `.sha256` and `.json` sidecars of a finished video file.
+pub fn write_sidecars(
+    path: &Path,
+    caps: &CameraCaps,
+    frames: Option<usize>,
+) -> std::io::Result<()> {
+    let sha256 = recording_sidecars::sha256_file(path)?;
+    let streams = vec![json!({
+        "name": "video",
+        "width": caps.width,
+        "height": caps.height,
+        "framerate": format!("{}/{}", caps.framerate_numerator, caps.framerate_denominator),
+        "frames": frames,
+    })];
+    recording_sidecars::write(path, &sha256, streams)
+}
@@ -4,16 +4,37 @@ use pyo3::prelude::*;
 extern crate gstreamer as gst;
 use std::sync::{
     atomic::{AtomicBool, AtomicUsize},
-    Arc,
+    Arc, Mutex,
 };
 
 use gst::prelude::*;
 
+mod container;
+mod devices;
+mod encoder;
+mod frame_times;
+mod integrity;
+mod lsl;
 mod rtsp;
+mod source;
+
+pub use container::{Container, Segmenting};
+pub use devices::{
+    caps_list, list_cameras, list_cameras_with, nearest_caps, CameraInfo, DeviceProvider,
+    DeviceSelector, Fallback, GstDeviceProvider,
+};
+pub use encoder::{available_codecs, Codec, EncoderConfig, RateControl};
+pub use lsl::LIBLSL_ENV_VAR;
+pub use source::{PyVideoSource, VideoSource};
 
 pub mod prelude {
+    pub use crate::list_cameras;
     pub use crate::CameraCaps;
+    pub use crate::CameraInfo;
+    pub use crate::DeviceSelector;
+    pub use crate::EncoderConfig;
     pub use crate::Recorder;
+    pub use crate::VideoSource;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -80,6 +101,32 @@ pub fn create_usb_camera_source() -> Result<gst::Element, gst::glib::Error> {
     }
 }
 
+/// Create the camera source for a specific device.
+///
+/// The device monitor creates the element, so it is the right source plugin
+/// with the device already selected, whichever platform plugin found it.
+pub fn create_usb_camera_source_for(
+    camera: &CameraInfo,
+) -> Result<gst::Element, gst::glib::Error> {
+    let device = camera.gst_device.as_ref().ok_or_else(|| {
+        gst::glib::Error::new(
+            gst::ResourceError::NotFound,
+            &format!(
+                "Camera '{}' was not found by the device monitor",
+                camera.name
+            ),
+        )
+    })?;
+    device
+        .create_element(Some("usb-camera-source"))
+        .map_err(|e| {
+            gst::glib::Error::new(
+                gst::CoreError::Failed,
+                &format!("Failed to create source for '{}': {}", camera.name, e),
+            )
+        })
+}
+
 fn record(
     filename: &str,
     caps: &CameraCaps,
@@ -90,7 +137,16 @@ fn record(
     display: bool,
     utp_port: Option<u16>,
     rotate: Option<Rotation>,
-    bitrate: Option<u32>,
+    encoder: EncoderConfig,
+    container: Container,
+    segmenting: Option<Segmenting>,
+    segment_files: Arc<Mutex<Vec<String>>>,
+    frame_times: Option<std::path::PathBuf>,
+    frame_outlet: Option<lsl::FrameOutlet>,
+    checksums: bool,
+    source: VideoSource,
+    started: std::sync::mpsc::Sender<Result<(), String>>,
+    actual_caps: Arc<Mutex<Option<CameraCaps>>>,
 ) {
     // Initialize GStreamer
     gst::init().unwrap();
@@ -104,11 +160,34 @@ fn record(
     };
 
     // Create the elements
-    let source = create_usb_camera_source()
-        .unwrap_or_else(|_| {
-            panic!("Failed to create USB camera source. Ensure the appropriate GStreamer plugins are installed.");
-        });
+    let is_camera = source.is_camera();
+    let source = match source.create() {
+        Ok(source) => source,
+        Err(e) => {
+            let _ = started.send(Err(format!("Failed to create video source: {}", e)));
+            return;
+        }
+    };
     let caps_filter = gst::ElementFactory::make("capsfilter").build().unwrap();
+    // MJPEG frames are decoded so they can be rotated, displayed and encoded;
+    // other sources already deliver raw video
+    let decoder = (is_camera && caps.is_mjpeg())
+        .then(|| gst::ElementFactory::make("jpegdec").build().unwrap());
+    // unrotated MJPEG is recorded as the camera sent it
+    let passthrough = decoder.is_some()
+        && encoder.codec == Codec::Mjpeg
+        && rotation_angle == VideoOrientationMethod::Identity;
+    // the RTSP server streams from the encoded tee, so in passthrough only the
+    // display needs decoded frames
+    let decoded = !passthrough || display;
+    let encoding = match encoder.build(passthrough) {
+        Ok(encoding) => encoding,
+        Err(e) => {
+            let _ = started.send(Err(format!("Failed to create encoder: {}", e)));
+            return;
+        }
+    };
+    let jpeg_tee = gst::ElementFactory::make("tee").build().unwrap();
     let rotate = gst::ElementFactory::make("videoflip")
         .property("video-direction", &rotation_angle)
         .build()
@@ -122,14 +201,28 @@ fn record(
 
     let recorder_queue = gst::ElementFactory::make("queue").build().unwrap();
     recorder_queue.set_property("max-size-buffers", &1u32.to_value());
-    let encoder = gst::ElementFactory::make("x264enc")
-        .property_from_str("tune", "zerolatency")
-        .property_from_str("speed-preset", "ultrafast")
-        .build()
-        .unwrap();
-    let parser = gst::ElementFactory::make("h264parse").build().unwrap();
-    let muxer = gst::ElementFactory::make("matroskamux").build().unwrap();
-    let sink = gst::ElementFactory::make("filesink").build().unwrap();
+    let muxer = match container.muxer() {
+        Ok(muxer) => muxer,
+        Err(e) => {
+            let _ = started.send(Err(format!("Failed to create muxer: {}", e)));
+            return;
+        }
+    };
+
+    let frame_count = last_frame_count.clone();
+
+    let frame_log = match frame_times
+        .as_deref()
+        .map(|path| frame_times::FrameLog::create(path, caps))
+        .transpose()
+    {
+        Ok(frame_log) => Arc::new(Mutex::new(frame_log)),
+        Err(e) => {
+            let _ = started.send(Err(format!("Failed to create frame times file: {}", e)));
+            return;
+        }
+    };
+    let probe_frame_log = frame_log.clone();
 
     // Add a probe to the source to capture frame timestamps
     source
@@ -137,14 +230,45 @@ fn record(
         .unwrap()
         .add_probe(
             gst::PadProbeType::BUFFER | gst::PadProbeType::PUSH,
-            move |_, probe_info| {
+            move |pad, probe_info| {
                 let time = probe_info.buffer().and_then(|buf| buf.pts());
                 if let Some(time) = time {
                     // Convert to seconds and store in the atomic variable
                     let seconds = time.seconds_f64();
                     last_frame_time.store(seconds, std::sync::atomic::Ordering::Relaxed);
                     // Increment the frame count
-                    last_frame_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let index =
+                        last_frame_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let running_time = pad
+                        .sticky_event::<gst::event::Segment>(0)
+                        .and_then(|event| {
+                            event
+                                .segment()
+                                .downcast_ref::<gst::ClockTime>()
+                                .and_then(|segment| segment.to_running_time(time))
+                        });
+
+                    // the frame was captured at base time + running time on the
+                    // pipeline clock; its age maps that onto the system clocks
+                    let age = running_time
+                        .and_then(|running_time| {
+                            let element = pad.parent_element()?;
+                            let captured = element.base_time()? + running_time;
+                            let now = element.current_clock_time()?;
+                            Some(now.seconds_f64() - captured.seconds_f64())
+                        })
+                        .unwrap_or_default();
+
+                    if let Some(frame_outlet) = &frame_outlet {
+                        if let Err(e) = frame_outlet.push(index, seconds, age) {
+                            eprintln!("Failed to push frame time: {}", e);
+                        }
+                    }
+                    if let Some(frame_log) = probe_frame_log.lock().unwrap().as_mut() {
+                        if let Err(e) = frame_log.log(index, time, running_time, age) {
+                            eprintln!("Failed to write frame time: {}", e);
+                        }
+                    }
                 }
 
                 gst::PadProbeReturn::Ok
@@ -157,48 +281,52 @@ fn record(
     // source.set_property("enable-quirks", true);
 
     // Set properties
-    caps_filter.set_property(
-        "caps",
-        &gst::Caps::builder("video/x-raw")
-            // .field("format", &caps.format)
-            .field("width", caps.width)
-            .field("height", caps.height)
-            .field(
-                "framerate",
-                &gst::Fraction::new(caps.framerate_numerator, caps.framerate_denominator),
-            )
-            .build(),
-    );
-
-    sink.set_property("location", filename.to_value());
-
-    // set bitrate for encoder to 8500 kbps
-    if let Some(bitrate) = bitrate {
-        encoder.set_property("bitrate", &bitrate);
+    if is_camera || !caps.is_mjpeg() {
+        caps_filter.set_property("caps", &caps.to_gst_caps());
+    } else {
+        let raw = CameraCaps {
+            format: String::new(),
+            ..caps.clone()
+        };
+        caps_filter.set_property("caps", &raw.to_gst_caps());
     }
 
+    // splitmuxsink drives its own muxer and file sinks
+    let (muxer, sink) = match &segmenting {
+        Some(segmenting) => (None, segmenting.sink(filename, &muxer)),
+        None => {
+            let sink = gst::ElementFactory::make("filesink").build().unwrap();
+            sink.set_property("location", filename.to_value());
+            (Some(muxer), sink)
+        }
+    };
+
     // Create the empty pipeline
     let pipeline = gst::Pipeline::default();
 
     // Build the pipeline
     pipeline
-        .add_many(&[
-            &source,
-            &caps_filter,
-            &rotate,
-            &tee,
-            &videoconvert,
-            &recorder_queue,
-            &encoder,
-            &tee2,
-            &parser,
-            &muxer,
-            &sink,
-        ])
+        .add_many(&[&source, &caps_filter, &recorder_queue, &tee2, &sink])
         .unwrap();
+    if decoded {
+        pipeline.add_many(&[&rotate, &tee]).unwrap();
+    }
 
     // link the source to the caps filter and the tee
-    gst::Element::link_many(&[&source, &caps_filter, &rotate, &tee]).unwrap();
+    if passthrough {
+        pipeline.add(&jpeg_tee).unwrap();
+        gst::Element::link_many(&[&source, &caps_filter, &jpeg_tee]).unwrap();
+        if decoded {
+            let decoder = decoder.as_ref().unwrap();
+            pipeline.add(decoder).unwrap();
+            gst::Element::link_many(&[&jpeg_tee, decoder, &rotate, &tee]).unwrap();
+        }
+    } else if let Some(decoder) = &decoder {
+        pipeline.add(decoder).unwrap();
+        gst::Element::link_many(&[&source, &caps_filter, decoder, &rotate, &tee]).unwrap();
+    } else {
+        gst::Element::link_many(&[&source, &caps_filter, &rotate, &tee]).unwrap();
+    }
 
     // add and link the display pipeline
     if display {
@@ -210,17 +338,26 @@ fn record(
 
 
     // link the recording pipeline
-    gst::Element::link_many(&[
-        &tee,
-        &recorder_queue,
-        &videoconvert,
-        &encoder,
-        &tee2,
-        &parser,
-        &muxer,
-        &sink,
-    ])
-    .unwrap();
+    let mut recording = vec![if passthrough { &jpeg_tee } else { &tee }, &recorder_queue];
+    if !passthrough {
+        pipeline.add(&videoconvert).unwrap();
+        recording.push(&videoconvert);
+    }
+    for element in [&encoding.encoder, &encoding.profile_filter].into_iter().flatten() {
+        pipeline.add(element).unwrap();
+        recording.push(element);
+    }
+    recording.push(&tee2);
+    if let Some(parser) = &encoding.parser {
+        pipeline.add(parser).unwrap();
+        recording.push(parser);
+    }
+    if let Some(muxer) = &muxer {
+        pipeline.add(muxer).unwrap();
+        recording.push(muxer);
+    }
+    recording.push(&sink);
+    gst::Element::link_many(&recording).unwrap();
 
     // if utp_port is specified, add utp server
     if let Some(port) = utp_port {
@@ -324,29 +461,66 @@ fn record(
     }
 
 
-    // Start playing
-    pipeline.set_state(gst::State::Playing).unwrap();
+    // Start playing; a missing or busy device fails here
+    if let Err(e) = pipeline.set_state(gst::State::Playing) {
+        let detail = pipeline
+            .bus()
+            .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]))
+            .and_then(|msg| match msg.view() {
+                gst::MessageView::Error(err) => Some(err.error().to_string()),
+                _ => None,
+            })
+            .unwrap_or_else(|| e.to_string());
+        let _ = pipeline.set_state(gst::State::Null);
+        let _ = started.send(Err(detail));
+        return;
+    }
 
     // Wait until error or EOS
     let bus = pipeline.bus().unwrap();
+    // set when the source itself ends, e.g. at the end of a replayed file
+    let mut finished_cleanly = false;
     loop {
         for msg in bus.iter() {
             match msg.view() {
-                gst::MessageView::Eos(..) => break,
+                gst::MessageView::Eos(..) => {
+                    finished_cleanly = true;
+                    break;
+                }
                 gst::MessageView::Error(err) => {
                     eprintln!(
                         "Error from {:?}: {}",
                         err.src().map(|s| s.path_string()),
                         err.error()
                     );
+                    // e.g. caps the camera doesn't support; Recorder::new is still waiting
+                    if !is_recording.load(std::sync::atomic::Ordering::Relaxed) {
+                        let _ = pipeline.set_state(gst::State::Null);
+                        let _ = started.send(Err(err.error().to_string()));
+                        return;
+                    }
                     break;
                 }
+                gst::MessageView::Element(element) => {
+                    if let Some(structure) = element.structure() {
+                        if structure.name().as_str() == "splitmuxsink-fragment-opened" {
+                            if let Ok(location) = structure.get::<String>("location") {
+                                segment_files.lock().unwrap().push(location);
+                            }
+                        }
+                    }
+                }
                 gst::MessageView::StateChanged(s) => {
                     match s.current() {
                         gst::State::Playing => {
                             // chexk if state change pertains to whole pipeline
                             if s.src().map(|s| s.path_string()).unwrap() == "/GstPipeline:pipeline0"
                             {
+                                let negotiated = caps_filter
+                                    .static_pad("src")
+                                    .and_then(|pad| pad.current_caps())
+                                    .and_then(|c| caps_list(&c).into_iter().next());
+                                *actual_caps.lock().unwrap() = negotiated;
                                 is_recording.store(true, std::sync::atomic::Ordering::Relaxed);
                             }
                         }
@@ -364,33 +538,62 @@ fn record(
                 }
             }
         }
-        if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
+        if finished_cleanly || stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
             break;
         }
     }
 
-    // Send end-of-stream (EOS)
-    pipeline.send_event(gst::event::Eos::new());
-
-    // Wait for the pipeline to finish
-    for msg in bus.iter_timed(gst::ClockTime::NONE) {
-        match msg.view() {
-            gst::MessageView::Eos(..) => break,
-            gst::MessageView::Error(err) => {
-                eprintln!(
-                    "Error from {:?}: {}",
-                    err.src().map(|s| s.path_string()),
-                    err.error()
-                );
-                break;
+    if !finished_cleanly {
+        // Send end-of-stream (EOS)
+        pipeline.send_event(gst::event::Eos::new());
+
+        // Wait for the pipeline to finish
+        for msg in bus.iter_timed(gst::ClockTime::NONE) {
+            match msg.view() {
+                gst::MessageView::Eos(..) => {
+                    finished_cleanly = true;
+                    break;
+                }
+                gst::MessageView::Error(err) => {
+                    eprintln!(
+                        "Error from {:?}: {}",
+                        err.src().map(|s| s.path_string()),
+                        err.error()
+                    );
+                    break;
+                }
+                _ => {}
             }
-            _ => {}
         }
     }
 
     // Set the pipeline to null state
     pipeline.set_state(gst::State::Null).unwrap();
 
+    if let Some(frame_log) = frame_log.lock().unwrap().as_mut() {
+        if let Err(e) = frame_log.flush() {
+            eprintln!("Failed to write frame times: {}", e);
+        }
+    }
+
+    // the file is complete once the muxer has seen EOS and the sink is closed
+    if checksums && finished_cleanly {
+        // the frame count covers the whole recording, not a single segment
+        let frames = match segmenting {
+            Some(_) => None,
+            None => Some(frame_count.load(std::sync::atomic::Ordering::Relaxed)),
+        };
+        let recorded_caps = actual_caps.lock().unwrap().clone();
+        let recorded_caps = recorded_caps.as_ref().unwrap_or(caps);
+        for file in segment_files.lock().unwrap().iter() {
+            if let Err(e) =
+                integrity::write_sidecars(std::path::Path::new(file), recorded_caps, frames)
+            {
+                eprintln!("Failed to write checksum of {}: {}", file, e);
+            }
+        }
+    }
+
     // Set is_recording to false
     is_recording.store(false, std::sync::atomic::Ordering::Relaxed);
 }
@@ -401,10 +604,12 @@ pub struct Recorder {
     last_frame_time: Arc<AtomicF64>,
     last_frame_count: Arc<AtomicUsize>,
     stop_flag: Arc<AtomicBool>,
+    actual_caps: Arc<Mutex<Option<CameraCaps>>>,
+    segment_files: Arc<Mutex<Vec<String>>>,
 }
 
-#[derive(Debug, Clone)]
-#[pyclass(name = "CameraCaps")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass(name = "CameraCaps", get_all)]
 pub struct CameraCaps {
     pub width: i32,
     pub height: i32,
@@ -413,6 +618,32 @@ pub struct CameraCaps {
     pub format: String,
 }
 
+impl CameraCaps {
+    /// Caps to request from the camera source.
+    ///
+    /// MJPEG formats request `image/jpeg`, anything else `video/x-raw` in the
+    /// given pixel format. An empty format leaves the raw format to negotiation.
+    pub fn to_gst_caps(&self) -> gst::Caps {
+        let framerate = gst::Fraction::new(self.framerate_numerator, self.framerate_denominator);
+        if self.is_mjpeg() {
+            return gst::Caps::builder("image/jpeg")
+                .field("width", self.width)
+                .field("height", self.height)
+                .field("framerate", framerate)
+                .build();
+        }
+        let builder = gst::Caps::builder("video/x-raw")
+            .field("width", self.width)
+            .field("height", self.height)
+            .field("framerate", framerate);
+        if self.format.is_empty() {
+            builder.build()
+        } else {
+            builder.field("format", &self.format).build()
+        }
+    }
+}
+
 #[pymethods]
 impl CameraCaps {
     #[new]
@@ -431,12 +662,32 @@ impl CameraCaps {
             format,
         }
     }
+
+    /// Whether these caps select the camera's MJPEG mode.
+    pub fn is_mjpeg(&self) -> bool {
+        matches!(
+            self.format.to_ascii_uppercase().as_str(),
+            "MJPEG" | "MJPG" | "JPEG" | "IMAGE/JPEG"
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CameraCaps({}x{} @ {}/{} {})",
+            self.width,
+            self.height,
+            self.framerate_numerator,
+            self.framerate_denominator,
+            self.format
+        )
+    }
 }
 
 #[pymethods]
 impl Recorder {
     #[new]
-    #[pyo3(signature = (caps, filename, display = false, utp_port = None, rotate = 0, bitrate = None))]
+    #[pyo3(signature = (caps, filename, display = false, utp_port = None, rotate = 0, bitrate = None, checksums = false, device = None, fallback = None, source = None, encoder = None, container = None, segment_duration = None, segment_size = None, frame_times = None, lsl_stream = None))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         caps: CameraCaps,
         filename: String,
@@ -444,6 +695,16 @@ impl Recorder {
         utp_port: Option<u16>,
         rotate: u32,
         bitrate: Option<u32>,
+        checksums: bool,
+        device: Option<DeviceSelector>,
+        fallback: Option<&str>,
+        source: Option<VideoSource>,
+        encoder: Option<EncoderConfig>,
+        container: Option<&str>,
+        segment_duration: Option<f64>,
+        segment_size: Option<u64>,
+        frame_times: Option<std::path::PathBuf>,
+        lsl_stream: Option<String>,
     ) -> PyResult<Self> {
         // run record in a new thread
         let stop_flag = Arc::new(AtomicBool::new(false));
@@ -467,6 +728,127 @@ impl Recorder {
             }
         };
 
+        let encoder = match (encoder, bitrate) {
+            (Some(encoder), None) => encoder,
+            (None, bitrate) => EncoderConfig {
+                rate_control: bitrate.map(RateControl::Cbr),
+                ..EncoderConfig::default()
+            },
+            (Some(_), Some(_)) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "Pass the bitrate as part of the encoder config",
+                ))
+            }
+        };
+        let container = match container {
+            Some(container) => container
+                .parse::<Container>()
+                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+            None => {
+                Container::from_path(std::path::Path::new(&filename)).unwrap_or(Container::Mkv)
+            }
+        };
+        if !container.supports(encoder.codec) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "{} can't be stored in {}",
+                encoder.codec, container
+            )));
+        }
+        let segmenting = match (segment_duration, segment_size) {
+            (None, None) => None,
+            (max_duration, max_bytes) => {
+                if !filename.contains('%') {
+                    return Err(pyo3::exceptions::PyValueError::new_err(
+                        "Segmented recordings need a filename pattern such as video_%03d.mkv",
+                    ));
+                }
+                let max_duration = max_duration
+                    .map(std::time::Duration::try_from_secs_f64)
+                    .transpose()
+                    .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+                Some(Segmenting {
+                    max_duration,
+                    max_bytes,
+                })
+            }
+        };
+        // a single file is known upfront, segments are added as they are opened
+        let segment_files = Arc::new(Mutex::new(match segmenting {
+            Some(_) => Vec::new(),
+            None => vec![filename.clone()],
+        }));
+        let segment_files_clone = segment_files.clone();
+        // the RTSP server payloads H.264
+        if utp_port.is_some() && encoder.codec != Codec::H264 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Streaming requires the h264 codec",
+            ));
+        }
+
+        let selector = match (&source, device) {
+            (None, device) => device,
+            (Some(VideoSource::Camera(selector)), None) => selector.clone(),
+            (Some(_), None) => None,
+            (Some(_), Some(_)) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "Pass either a source or a device, not both",
+                ))
+            }
+        };
+        let camera = selector
+            .map(|device| device.resolve())
+            .transpose()
+            .map_err(|e| {
+                pyo3::exceptions::PyRuntimeError::new_err(format!(
+                    "Failed to select camera: {}",
+                    e
+                ))
+            })?;
+        let fallback = match fallback {
+            None => None,
+            Some("resolution") => Some(Fallback::Resolution),
+            Some("framerate") => Some(Fallback::Framerate),
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "Invalid fallback value",
+                ))
+            }
+        };
+        let source = match source {
+            None | Some(VideoSource::Camera(_)) => {
+                VideoSource::Camera(camera.clone().map(DeviceSelector::Camera))
+            }
+            Some(source) => source,
+        };
+        // replace unsupported caps with the nearest mode of the selected (or first) camera
+        let caps = match fallback {
+            Some(fallback) if source.is_camera() => {
+                let supported = match &camera {
+                    Some(camera) => camera.caps.clone(),
+                    None => list_cameras()
+                        .ok()
+                        .and_then(|cameras| cameras.into_iter().next())
+                        .map(|camera| camera.caps)
+                        .unwrap_or_default(),
+                };
+                nearest_caps(&caps, &supported, fallback).unwrap_or(caps)
+            }
+            _ => caps,
+        };
+        // created here so a missing liblsl fails the constructor
+        let frame_outlet = lsl_stream
+            .map(|name| lsl::FrameOutlet::new(&name, &filename, &caps))
+            .transpose()
+            .map_err(|e| {
+                pyo3::exceptions::PyRuntimeError::new_err(format!(
+                    "Failed to create LSL outlet: {}",
+                    e
+                ))
+            })?;
+        let (started, start_result) = std::sync::mpsc::channel();
+        let actual_caps = Arc::new(Mutex::new(None));
+        let actual_caps_clone = actual_caps.clone();
+
         std::thread::spawn(move || {
             let caps = caps.clone();
             record(
@@ -479,13 +861,35 @@ impl Recorder {
                 display,
                 utp_port,
                 r,
-                bitrate,
+                encoder,
+                container,
+                segmenting,
+                segment_files_clone,
+                frame_times,
+                frame_outlet,
+                checksums,
+                source,
+                started,
+                actual_caps_clone,
             );
         });
 
         // wait for recording to start
         while !is_recording.load(std::sync::atomic::Ordering::Relaxed) {
-            std::thread::sleep(std::time::Duration::from_millis(10));
+            let error = match start_result.try_recv() {
+                Ok(Err(e)) => e,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    "recording thread exited".to_string()
+                }
+                _ => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    continue;
+                }
+            };
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "Failed to start camera: {}",
+                error
+            )));
         }
 
         Ok(Recorder {
@@ -493,9 +897,21 @@ impl Recorder {
             stop_flag,
             last_frame_time,
             last_frame_count,
+            actual_caps,
+            segment_files,
         })
     }
 
+    /// Files written so far: the recording itself, or every segment opened.
+    pub fn segments(&self) -> Vec<String> {
+        self.segment_files.lock().unwrap().clone()
+    }
+
+    /// The caps negotiated with the camera, once recording has started.
+    pub fn actual_caps(&self) -> Option<CameraCaps> {
+        self.actual_caps.lock().unwrap().clone()
+    }
+
     pub fn stop(&self) {
         self.stop_flag
             .store(true, std::sync::atomic::Ordering::Relaxed);
@@ -536,10 +952,34 @@ impl Recorder {
     }
 }
 
+/// List the attached cameras and their supported formats.
+#[pyfunction]
+#[pyo3(name = "list_cameras")]
+fn py_list_cameras() -> PyResult<Vec<CameraInfo>> {
+    list_cameras().map_err(|e| {
+        pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to list cameras: {}", e))
+    })
+}
+
+/// Names of the codecs with an installed encoder.
+#[pyfunction]
+#[pyo3(name = "available_codecs")]
+fn py_available_codecs() -> Vec<String> {
+    available_codecs()
+        .iter()
+        .map(|codec| codec.to_string())
+        .collect()
+}
+
 /// A Python module implemented in Rust.
 #[pymodule]
 fn webcam_py(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Recorder>()?;
     m.add_class::<CameraCaps>()?;
+    m.add_class::<CameraInfo>()?;
+    m.add_class::<PyVideoSource>()?;
+    m.add_class::<EncoderConfig>()?;
+    m.add_function(wrap_pyfunction!(py_available_codecs, m)?)?;
+    m.add_function(wrap_pyfunction!(py_list_cameras, m)?)?;
     Ok(())
 }
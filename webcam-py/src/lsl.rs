@@ -0,0 +1,67 @@
+//! LSL outlet of captured frame times, so LabRecorder stores the video clock
+//! next to the other streams.
+//!
+//! liblsl comes from `liblsl-loader`, shared with lsl-recorder. The library is
+//! taken from the `WEBCAM_PY_LIBLSL` environment variable, or else from the
+//! system library path.
+
+use liblsl_loader::{Outlet, StreamHeader, CHANNEL_FORMAT_DOUBLE};
+
+use crate::CameraCaps;
+
+/// Environment variable that overrides the liblsl location.
+pub const LIBLSL_ENV_VAR: &str = "WEBCAM_PY_LIBLSL";
+
+const CHANNELS: [&str; 2] = ["frame", "pts"];
+
+/// A stream with one `[frame index, PTS]` sample per captured frame, at the
+/// camera's nominal rate.
+pub(crate) struct FrameOutlet {
+    outlet: Outlet,
+}
+
+impl FrameOutlet {
+    /// Create the outlet; the header records the video file and its format.
+    pub(crate) fn new(
+        name: &str,
+        filename: &str,
+        caps: &CameraCaps,
+    ) -> Result<Self, std::io::Error> {
+        let api = liblsl_loader::load(LIBLSL_ENV_VAR, &[])?;
+        let nominal_rate = if caps.framerate_denominator > 0 {
+            caps.framerate_numerator as f64 / caps.framerate_denominator as f64
+        } else {
+            0.0
+        };
+        let source_id = format!("webcam-py-{}-{}", std::process::id(), filename);
+        let (width, height) = (caps.width.to_string(), caps.height.to_string());
+        let outlet = Outlet::new(
+            api,
+            &StreamHeader {
+                name,
+                kind: "VideoFrames",
+                channel_count: CHANNELS.len(),
+                nominal_rate,
+                channel_format: CHANNEL_FORMAT_DOUBLE,
+                source_id: &source_id,
+                channel_labels: &CHANNELS,
+                desc: &[
+                    ("filename", filename),
+                    ("width", &width),
+                    ("height", &height),
+                    ("format", &caps.format),
+                ],
+            },
+        )?;
+
+        Ok(FrameOutlet { outlet })
+    }
+
+    /// Push the sample of frame `index`, stamped with its capture time on the
+    /// LSL clock: the current LSL time minus `age`, the seconds since capture
+    /// on the pipeline clock.
+    pub(crate) fn push(&self, index: usize, pts: f64, age: f64) -> Result<(), std::io::Error> {
+        let timestamp = self.outlet.local_clock() - age;
+        self.outlet.push_doubles(&[index as f64, pts], timestamp)
+    }
+}
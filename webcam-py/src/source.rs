@@ -0,0 +1,183 @@
+//! Where a `Recorder` gets its frames from.
+
+use std::path::PathBuf;
+
+use gst::prelude::*;
+use pyo3::prelude::*;
+
+use crate::{create_usb_camera_source, create_usb_camera_source_for, DeviceSelector};
+
+// normalises decoded or synthetic video to whatever the caps filter asks for
+const CONFORM: &str = "videoconvert ! videoscale ! videorate";
+
+/// Video input of a recording.
+///
+/// Everything except `Camera` delivers raw video, so MJPEG caps only apply to
+/// cameras; the test pattern and file sources run without any hardware.
+#[derive(Debug, Clone)]
+pub enum VideoSource {
+    /// The platform USB camera source, optionally a specific device.
+    Camera(Option<DeviceSelector>),
+    /// `videotestsrc` with the given pattern, e.g. `smpte` or `ball`.
+    TestPattern(String),
+    /// Replay of a video file in real time.
+    File(PathBuf),
+    /// Network camera, e.g. an `rtsp://` or HTTP MJPEG URL.
+    Network(String),
+    /// X11 screen capture, optionally of a specific display such as `:0`.
+    Screen(Option<String>),
+    /// A `gst-launch` fragment whose last element has an unlinked src pad.
+    Launch(String),
+}
+
+impl Default for VideoSource {
+    fn default() -> Self {
+        VideoSource::Camera(None)
+    }
+}
+
+impl VideoSource {
+    pub fn is_camera(&self) -> bool {
+        matches!(self, VideoSource::Camera(_))
+    }
+
+    /// Build the source element; its `src` pad is linked to the caps filter.
+    pub fn create(&self) -> Result<gst::Element, gst::glib::Error> {
+        gst::init()?;
+
+        let description = match self {
+            VideoSource::Camera(None) => return create_usb_camera_source(),
+            VideoSource::Camera(Some(DeviceSelector::Camera(camera))) => {
+                return create_usb_camera_source_for(camera)
+            }
+            VideoSource::Camera(Some(selector)) => {
+                return create_usb_camera_source_for(&selector.resolve()?)
+            }
+            VideoSource::TestPattern(pattern) => {
+                format!(
+                    "videotestsrc is-live=true pattern={} ! {}",
+                    pattern, CONFORM
+                )
+            }
+            VideoSource::File(path) => {
+                let path = std::fs::canonicalize(path).map_err(|e| {
+                    gst::glib::Error::new(
+                        gst::ResourceError::NotFound,
+                        &format!("{}: {}", path.display(), e),
+                    )
+                })?;
+                let uri = gst::glib::filename_to_uri(&path, None)?;
+                return uri_source(&uri, &format!("{} ! identity sync=true", CONFORM));
+            }
+            VideoSource::Network(url) => return uri_source(url, CONFORM),
+            VideoSource::Screen(display) => match display {
+                Some(display) => format!(
+                    "ximagesrc display-name=\"{}\" use-damage=false ! {}",
+                    display, CONFORM
+                ),
+                None => format!("ximagesrc use-damage=false ! {}", CONFORM),
+            },
+            VideoSource::Launch(description) => description.clone(),
+        };
+
+        let bin = gst::parse::bin_from_description(&description, true)?;
+        bin.set_property("name", "video-source");
+        Ok(bin.upcast())
+    }
+}
+
+/// `uridecodebin` for `uri`, linked to the `conform` fragment once it exposes a
+/// video pad. The URI is set as a property, so it is never parsed as part of a
+/// launch description.
+fn uri_source(uri: &str, conform: &str) -> Result<gst::Element, gst::glib::Error> {
+    let failed =
+        |e: gst::glib::BoolError| gst::glib::Error::new(gst::CoreError::Failed, &e.to_string());
+
+    let decode = gst::ElementFactory::make("uridecodebin")
+        .property("uri", uri)
+        .build()
+        .map_err(|e| gst::glib::Error::new(gst::CoreError::MissingPlugin, &e.to_string()))?;
+    let conform = gst::parse::bin_from_description(conform, true)?;
+    let bin = gst::Bin::builder().name("video-source").build();
+    bin.add_many([&decode, conform.upcast_ref()])
+        .map_err(failed)?;
+    let src = conform.static_pad("src").unwrap();
+    bin.add_pad(&gst::GhostPad::with_target(&src).map_err(failed)?)
+        .map_err(failed)?;
+
+    let sink = conform.static_pad("sink").unwrap();
+    decode.connect_pad_added(move |_, pad| {
+        let is_video = pad
+            .current_caps()
+            .and_then(|caps| {
+                caps.structure(0)
+                    .map(|s| s.name().as_str().starts_with("video/"))
+            })
+            .unwrap_or(false);
+        if is_video && !sink.is_linked() {
+            if let Err(e) = pad.link(&sink) {
+                eprintln!("Failed to link decoded video: {}", e);
+            }
+        }
+    });
+    Ok(bin.upcast())
+}
+
+/// Python handle for a [`VideoSource`], created through its static methods.
+#[derive(Debug, Clone)]
+#[pyclass(name = "VideoSource")]
+pub struct PyVideoSource {
+    pub source: VideoSource,
+}
+
+#[pymethods]
+impl PyVideoSource {
+    #[staticmethod]
+    #[pyo3(signature = (device = None))]
+    fn camera(device: Option<DeviceSelector>) -> Self {
+        VideoSource::Camera(device).into()
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (pattern = "smpte".to_string()))]
+    fn test_pattern(pattern: String) -> Self {
+        VideoSource::TestPattern(pattern).into()
+    }
+
+    #[staticmethod]
+    fn file(path: PathBuf) -> Self {
+        VideoSource::File(path).into()
+    }
+
+    #[staticmethod]
+    fn network(url: String) -> Self {
+        VideoSource::Network(url).into()
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (display = None))]
+    fn screen(display: Option<String>) -> Self {
+        VideoSource::Screen(display).into()
+    }
+
+    #[staticmethod]
+    fn launch(description: String) -> Self {
+        VideoSource::Launch(description).into()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("VideoSource({:?})", self.source)
+    }
+}
+
+impl From<VideoSource> for PyVideoSource {
+    fn from(source: VideoSource) -> Self {
+        PyVideoSource { source }
+    }
+}
+
+impl<'source> FromPyObject<'source> for VideoSource {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        Ok(ob.extract::<PyVideoSource>()?.source)
+    }
+}
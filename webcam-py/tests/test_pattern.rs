@@ -0,0 +1,73 @@
+//! Records from the test pattern source. Needs GStreamer and the elements of
+//! the default pipeline, so it only runs with `cargo test -- --ignored`.
+
+use std::time::Duration;
+
+use webcam_py::prelude::*;
+
+// source, conversion, default encoder and Matroska muxer
+const REQUIRED_ELEMENTS: [&str; 6] = [
+    "videotestsrc",
+    "videoconvert",
+    "videoscale",
+    "videorate",
+    "x264enc",
+    "matroskamux",
+];
+
+fn gstreamer_available() -> bool {
+    gstreamer::init().is_ok()
+        && REQUIRED_ELEMENTS
+            .iter()
+            .all(|name| gstreamer::ElementFactory::find(name).is_some())
+}
+
+#[test]
+#[ignore = "needs GStreamer with videotestsrc, x264enc and matroskamux"]
+fn records_the_test_pattern_to_a_file() {
+    assert!(
+        gstreamer_available(),
+        "GStreamer or one of {:?} is not installed",
+        REQUIRED_ELEMENTS
+    );
+    // errors are formatted through the interpreter
+    pyo3::prepare_freethreaded_python();
+
+    let filename = std::env::temp_dir().join(format!("webcam-py-test-{}.mkv", std::process::id()));
+    let caps = CameraCaps {
+        width: 320,
+        height: 240,
+        framerate_numerator: 30,
+        framerate_denominator: 1,
+        format: "I420".to_string(),
+    };
+    let recorder = Recorder::new(
+        caps,
+        filename.to_string_lossy().into_owned(),
+        false,
+        None,
+        0,
+        None,
+        false,
+        None,
+        None,
+        Some(VideoSource::TestPattern("smpte".to_string())),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .expect("Failed to create recorder");
+    std::thread::sleep(Duration::from_secs(3));
+    recorder.stop();
+
+    let size = std::fs::metadata(&filename)
+        .expect("recording was not written")
+        .len();
+    let frames = recorder.last_frame_count();
+    std::fs::remove_file(&filename).unwrap();
+    assert!(size > 0, "recording is empty");
+    assert!(frames > 0, "no frames were recorded");
+}